@@ -0,0 +1,29 @@
+//! Headless server for online co-op, runs the world for two clients without opening a window.
+//! Listens on the address given as the first argument, `127.0.0.1:7878` by default.
+
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    use macroquad::prelude::*;
+    use singularity_survivor::net::{server::Server, DEFAULT_ADDRESS};
+
+    rand::srand(miniquad::date::now() as u64);
+    let address = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+    let server = match Server::bind(&address) {
+        Ok(server) => server,
+        Err(err) => {
+            error!("Failed to listen on {}: {}", address, err);
+            std::process::exit(1);
+        }
+    };
+    info!("Waiting for two players on {}", address);
+    if let Err(err) = server.run() {
+        error!("The server stopped: {}", err);
+        std::process::exit(1);
+    }
+}
+
+// the web build can't listen for connections
+#[cfg(target_arch = "wasm32")]
+fn main() {}
@@ -0,0 +1,8 @@
+#[cfg(not(target_arch = "wasm32"))]
+pub mod net;
+pub mod states;
+pub mod utils;
+
+pub trait GameState {
+    fn do_frame(&mut self) -> Option<Box<dyn GameState>>;
+}
@@ -1,12 +1,5 @@
 use macroquad::prelude::*;
-use states::menu_state::MenuState;
-
-mod states;
-mod utils;
-
-pub trait GameState {
-    fn do_frame(&mut self) -> Option<Box<dyn GameState>>;
-}
+use singularity_survivor::{states::menu_state::MenuState, GameState};
 
 struct Game {
     main: Box<dyn GameState>,
@@ -15,7 +8,7 @@ struct Game {
 impl Game {
     pub fn new() -> Self {
         Self {
-            main: Box::new(MenuState::Initial),
+            main: Box::new(MenuState::initial()),
         }
     }
 
@@ -31,6 +24,7 @@ impl Game {
 
 #[macroquad::main("Asteroids")]
 async fn main() {
+    rand::srand(miniquad::date::now() as u64);
     let mut game = Game::new();
 
     loop {
@@ -0,0 +1,187 @@
+use std::collections::{HashMap, VecDeque};
+
+use macroquad::prelude::*;
+
+use super::protocol::{ShapeState, ShipState, Snapshot, SNAPSHOT_INTERVAL, TICK_RATE};
+
+/// The world is shown this many ticks behind the newest snapshot, so that there is almost always
+/// a later one to interpolate towards
+const INTERPOLATION_DELAY: f32 = 2. * SNAPSHOT_INTERVAL as f32;
+/// Further off than this many ticks the shown time jumps instead of catching up
+const MAX_DRIFT: f32 = 30.;
+
+/// Snapshots received from the server, shown a little in the past
+#[derive(Default)]
+pub struct SnapshotBuffer {
+    snapshots: VecDeque<Snapshot>,
+    /// shapes of the asteroids in the last snapshot, the server sends each only once
+    shapes: HashMap<u32, ShapeState>,
+    /// server tick that is shown, in fractions of ticks
+    render_tick: f32,
+}
+
+impl SnapshotBuffer {
+    pub fn push(&mut self, mut snapshot: Snapshot) {
+        if let Some(latest) = self.snapshots.back() {
+            if snapshot.tick <= latest.tick {
+                return;
+            }
+        }
+
+        let mut shapes = HashMap::new();
+        for asteroid in snapshot.asteroids.iter_mut() {
+            if asteroid.shape.is_none() {
+                asteroid.shape = self.shapes.remove(&asteroid.id);
+            }
+            if let Some(shape) = &asteroid.shape {
+                shapes.insert(asteroid.id, shape.clone());
+            }
+        }
+        self.shapes = shapes;
+        self.snapshots.push_back(snapshot);
+    }
+
+    pub fn latest(&self) -> Option<&Snapshot> {
+        self.snapshots.back()
+    }
+
+    /// Moves the shown time on by `frame_time` seconds and returns the world at that time
+    pub fn advance(&mut self, frame_time: f32) -> Option<Snapshot> {
+        let latest = self.snapshots.back()?.tick as f32;
+
+        // follow the server's clock smoothly, but jump after a hiccup
+        self.render_tick += frame_time * TICK_RATE;
+        let drift = latest - INTERPOLATION_DELAY - self.render_tick;
+        if drift.abs() > MAX_DRIFT {
+            self.render_tick += drift;
+        } else {
+            self.render_tick += drift * 0.05;
+        }
+
+        while self.snapshots.len() > 2 && self.snapshots[1].tick as f32 <= self.render_tick {
+            self.snapshots.pop_front();
+        }
+
+        let from = &self.snapshots[0];
+        match self.snapshots.get(1) {
+            Some(to) if self.render_tick > from.tick as f32 => {
+                let span = (to.tick - from.tick) as f32;
+                let alpha = (self.render_tick - from.tick as f32) / span;
+                Some(interpolate(from, to, alpha.min(1.)))
+            }
+            _ => Some(from.clone()),
+        }
+    }
+}
+
+/// The world at `alpha` of the way from snapshot `from` to snapshot `to`. Things with an id move
+/// between their two positions, things without one are moved back from `to` by their velocity.
+pub fn interpolate(from: &Snapshot, to: &Snapshot, alpha: f32) -> Snapshot {
+    let lerp = |a: f32, b: f32| a + (b - a) * alpha;
+    let before_to = (1. - alpha) * (to.tick - from.tick) as f32;
+    let back = |pos: Vec2, vel: Vec2| pos - vel * before_to;
+
+    let mut snapshot = to.clone();
+    snapshot.game_t = lerp(from.game_t, to.game_t);
+
+    for (ship, from) in snapshot.ships.iter_mut().zip(from.ships.iter()) {
+        interpolate_ship(ship, from, alpha);
+    }
+
+    let asteroids: HashMap<_, _> = from.asteroids.iter().map(|a| (a.id, a)).collect();
+    for asteroid in snapshot.asteroids.iter_mut() {
+        if let Some(from) = asteroids.get(&asteroid.id) {
+            asteroid.pos = from.pos.lerp(asteroid.pos, alpha);
+            asteroid.rot = lerp(from.rot, asteroid.rot);
+        }
+    }
+    let bullets: HashMap<_, _> = from.bullets.iter().map(|b| (b.id, b)).collect();
+    for bullet in snapshot.bullets.iter_mut() {
+        if let Some(from) = bullets.get(&bullet.id) {
+            bullet.pos = from.pos.lerp(bullet.pos, alpha);
+        }
+    }
+    let rockets: HashMap<_, _> = from.rockets.iter().map(|r| (r.id, r)).collect();
+    for rocket in snapshot.rockets.iter_mut() {
+        if let Some(from) = rockets.get(&rocket.id) {
+            rocket.pos = from.pos.lerp(rocket.pos, alpha);
+            rocket.rot = lerp(from.rot, rocket.rot);
+        }
+    }
+    let black_holes: HashMap<_, _> = from.black_holes.iter().map(|bh| (bh.id, bh)).collect();
+    for bh in snapshot.black_holes.iter_mut() {
+        if let Some(from) = black_holes.get(&bh.id) {
+            bh.pos = from.pos.lerp(bh.pos, alpha);
+            bh.mass = lerp(from.mass, bh.mass);
+        }
+    }
+
+    for white_hole in snapshot.white_holes.iter_mut() {
+        white_hole.pos = back(white_hole.pos, white_hole.vel);
+    }
+    for orb in snapshot.xp_orbs.iter_mut() {
+        orb.pos = back(orb.pos, orb.vel);
+    }
+    for particle in snapshot.radiation.iter_mut() {
+        particle.pos = back(particle.pos, particle.vel);
+    }
+    snapshot
+}
+
+fn interpolate_ship(ship: &mut ShipState, from: &ShipState, alpha: f32) {
+    // revived ships jump to their partner
+    if from.down.is_some() != ship.down.is_some() {
+        return;
+    }
+    ship.pos = from.pos.lerp(ship.pos, alpha);
+    ship.rot = from.rot + (ship.rot - from.rot) * alpha;
+    ship.t = from.t + (ship.t - from.t) * alpha;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::net::protocol::{AsteroidState, OrbState};
+
+    use super::*;
+
+    fn snapshot(tick: u32, x: f32) -> Snapshot {
+        Snapshot {
+            tick,
+            game_t: tick as f32 / TICK_RATE,
+            asteroids: vec![AsteroidState {
+                id: 1,
+                pos: Vec2::new(x, 0.),
+                shape: (tick == 0).then(ShapeState::default),
+                ..AsteroidState::default()
+            }],
+            xp_orbs: vec![OrbState {
+                pos: Vec2::new(x, 0.),
+                vel: Vec2::new(1., 0.),
+            }],
+            ..Snapshot::default()
+        }
+    }
+
+    #[test]
+    fn things_move_between_snapshots() {
+        let between = interpolate(&snapshot(0, 0.), &snapshot(4, 8.), 0.5);
+        assert_eq!(between.asteroids[0].pos, Vec2::new(4., 0.));
+        // orbs have no id and are moved back by their velocity
+        assert_eq!(between.xp_orbs[0].pos, Vec2::new(6., 0.));
+        assert_eq!(between.game_t, 2. / TICK_RATE);
+    }
+
+    #[test]
+    fn shapes_are_remembered() {
+        let mut buffer = SnapshotBuffer::default();
+        buffer.push(snapshot(0, 0.));
+        buffer.push(snapshot(3, 3.));
+        let latest = buffer.latest().unwrap();
+        assert!(latest.asteroids[0].shape.is_some());
+
+        // late snapshots are dropped
+        buffer.push(snapshot(2, 2.));
+        assert_eq!(buffer.latest().unwrap().tick, 3);
+        assert!(buffer.advance(1. / TICK_RATE).is_some());
+    }
+}
@@ -0,0 +1,87 @@
+//! Online co-op: a headless server runs the authoritative simulation and the clients send it
+//! their input over WebSocket. Only the native build has a transport, the web build would need
+//! the browser's WebSocket API instead of a `TcpStream` and doesn't offer online co-op yet.
+
+use std::{
+    io,
+    net::{TcpStream, ToSocketAddrs},
+};
+
+use tungstenite::{Message, WebSocket};
+
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+/// Where the server listens and the clients connect to, unless told otherwise
+pub const DEFAULT_ADDRESS: &str = "127.0.0.1:7878";
+
+/// Server the game connects to, set with the `SINGULARITY_SERVER` environment variable
+pub fn server_address() -> String {
+    std::env::var("SINGULARITY_SERVER").unwrap_or_else(|_| DEFAULT_ADDRESS.to_string())
+}
+
+/// A WebSocket that never blocks once the handshake is done
+pub struct Connection {
+    socket: WebSocket<TcpStream>,
+}
+
+impl Connection {
+    fn new(socket: WebSocket<TcpStream>) -> io::Result<Self> {
+        socket.get_ref().set_nonblocking(true)?;
+        socket.get_ref().set_nodelay(true)?;
+        Ok(Self { socket })
+    }
+
+    /// Does the server side of the handshake with a client that just connected
+    pub fn accept(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(false)?;
+        let socket = tungstenite::accept(stream).map_err(io::Error::other)?;
+        Self::new(socket)
+    }
+
+    /// Connects to a server at `address`, like "127.0.0.1:7878"
+    pub fn connect(address: &str) -> io::Result<Self> {
+        let addr = address
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, address.to_string()))?;
+        let stream = TcpStream::connect(addr)?;
+        let (socket, _) =
+            tungstenite::client(format!("ws://{}/", address), stream).map_err(io::Error::other)?;
+        Self::new(socket)
+    }
+
+    /// Queues a message and sends as much as the socket takes right now
+    pub fn send(&mut self, bytes: Vec<u8>) -> io::Result<()> {
+        match self.socket.send(Message::binary(bytes)) {
+            Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result.map_err(into_io_error),
+        }
+    }
+
+    /// The next message that arrived, `None` if there is none yet
+    pub fn receive(&mut self) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            match self.socket.read() {
+                Ok(Message::Binary(bytes)) => return Ok(Some(bytes.to_vec())),
+                // pings are answered by tungstenite, a close is reported by the next read
+                Ok(_) => {}
+                Err(tungstenite::Error::Io(err)) if err.kind() == io::ErrorKind::WouldBlock => {
+                    return Ok(None)
+                }
+                Err(err) => return Err(into_io_error(err)),
+            }
+        }
+    }
+}
+
+fn into_io_error(err: tungstenite::Error) -> io::Error {
+    match err {
+        tungstenite::Error::Io(err) => err,
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            io::ErrorKind::ConnectionAborted.into()
+        }
+        err => io::Error::other(err),
+    }
+}
@@ -0,0 +1,613 @@
+//! Messages between the online server and its clients. They travel as binary WebSocket
+//! messages in a compact little endian encoding, both ends are always the same build.
+
+use macroquad::prelude::*;
+
+use crate::states::{game_mode::GameMode, main_state::ShipInput, run_stats::RunStats};
+
+/// Simulated frames per second on the server, the clients send their input at the same rate
+pub const TICK_RATE: f32 = 60.;
+/// The server sends a snapshot every this many ticks
+pub const SNAPSHOT_INTERVAL: u32 = 3;
+
+/// Something that can be sent over the network
+pub trait Wire: Sized {
+    fn encode(&self, out: &mut Vec<u8>);
+    /// Reads the value from the start of `input` and advances it, `None` for truncated input
+    fn decode(input: &mut &[u8]) -> Option<Self>;
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        out
+    }
+
+    /// Decodes a whole message, trailing bytes make it invalid
+    fn from_bytes(mut bytes: &[u8]) -> Option<Self> {
+        let value = Self::decode(&mut bytes)?;
+        bytes.is_empty().then_some(value)
+    }
+}
+
+fn take<const N: usize>(input: &mut &[u8]) -> Option<[u8; N]> {
+    let (bytes, rest) = input.split_first_chunk::<N>()?;
+    *input = rest;
+    Some(*bytes)
+}
+
+impl Wire for u8 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        take::<1>(input).map(|[byte]| byte)
+    }
+}
+
+impl Wire for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        match u8::decode(input)? {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+impl Wire for u32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        take(input).map(u32::from_le_bytes)
+    }
+}
+
+impl Wire for usize {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (*self as u32).encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        u32::decode(input).map(|value| value as usize)
+    }
+}
+
+impl Wire for f32 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        take(input).map(f32::from_le_bytes)
+    }
+}
+
+impl Wire for Vec2 {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.x.encode(out);
+        self.y.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(Vec2::new(f32::decode(input)?, f32::decode(input)?))
+    }
+}
+
+impl Wire for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::decode(input)?;
+        if input.len() < len {
+            return None;
+        }
+        let (bytes, rest) = input.split_at(len);
+        *input = rest;
+        String::from_utf8(bytes.to_vec()).ok()
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.len().encode(out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let len = usize::decode(input)?;
+        // every item takes at least a byte, so a bogus length can't reserve lots of memory
+        if input.len() < len {
+            return None;
+        }
+        (0..len).map(|_| T::decode(input)).collect()
+    }
+}
+
+impl<T: Wire, const N: usize> Wire for [T; N] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        for item in self {
+            item.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let items: Option<Vec<T>> = (0..N).map(|_| T::decode(input)).collect();
+        items?.try_into().ok()
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.is_some().encode(out);
+        if let Some(value) = self {
+            value.encode(out);
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        if bool::decode(input)? {
+            T::decode(input).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+}
+
+impl<A: Wire, B: Wire> Wire for (A, B) {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.0.encode(out);
+        self.1.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some((A::decode(input)?, B::decode(input)?))
+    }
+}
+
+/// Implements [`Wire`] for a struct by encoding its fields in order
+macro_rules! wire_struct {
+    ($name:ident { $($field:ident),* $(,)? }) => {
+        impl Wire for $name {
+            fn encode(&self, out: &mut Vec<u8>) {
+                $(self.$field.encode(out);)*
+            }
+
+            fn decode(input: &mut &[u8]) -> Option<Self> {
+                Some(Self {
+                    $($field: Wire::decode(input)?,)*
+                })
+            }
+        }
+    };
+}
+
+/// Buttons are sent as a bit each
+impl Wire for ShipInput {
+    fn encode(&self, out: &mut Vec<u8>) {
+        let buttons = [
+            self.up,
+            self.down,
+            self.left,
+            self.right,
+            self.shoot,
+            self.rocket,
+            self.singularity,
+            self.confirm,
+        ];
+        let bits = buttons
+            .iter()
+            .enumerate()
+            .fold(0u8, |bits, (idx, &held)| bits | (held as u8) << idx);
+        bits.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        let bits = u8::decode(input)?;
+        let held = |idx: u8| bits & 1 << idx != 0;
+        Some(ShipInput {
+            up: held(0),
+            down: held(1),
+            left: held(2),
+            right: held(3),
+            shoot: held(4),
+            rocket: held(5),
+            singularity: held(6),
+            confirm: held(7),
+        })
+    }
+}
+
+/// Only what the menu shows of a co-op run is sent
+impl Wire for RunStats {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.time.encode(out);
+        self.level.encode(out);
+        self.asteroids_destroyed.encode(out);
+        self.slingshots.encode(out);
+        self.orbits.encode(out);
+        self.best_combo.encode(out);
+        self.maneuver_xp.encode(out);
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        Some(RunStats {
+            mode: GameMode::Online,
+            time: Wire::decode(input)?,
+            level: Wire::decode(input)?,
+            asteroids_destroyed: Wire::decode(input)?,
+            slingshots: Wire::decode(input)?,
+            orbits: Wire::decode(input)?,
+            best_combo: Wire::decode(input)?,
+            maneuver_xp: Wire::decode(input)?,
+            ..RunStats::default()
+        })
+    }
+}
+
+pub enum ClientMessage {
+    /// Buttons held in the client's tick number `seq`
+    Input { seq: u32, input: ShipInput },
+}
+
+impl Wire for ClientMessage {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            ClientMessage::Input { seq, input } => {
+                0u8.encode(out);
+                seq.encode(out);
+                input.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        match u8::decode(input)? {
+            0 => Some(ClientMessage::Input {
+                seq: Wire::decode(input)?,
+                input: Wire::decode(input)?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+pub enum ServerMessage {
+    /// Sent right after connecting, `player` is the index of the client's ship
+    Welcome {
+        player: usize,
+    },
+    Snapshot(Box<Snapshot>),
+    /// The run is over, the server starts a new one for the next players
+    GameOver(RunStats),
+}
+
+impl Wire for ServerMessage {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            ServerMessage::Welcome { player } => {
+                0u8.encode(out);
+                player.encode(out);
+            }
+            ServerMessage::Snapshot(snapshot) => {
+                1u8.encode(out);
+                snapshot.encode(out);
+            }
+            ServerMessage::GameOver(stats) => {
+                2u8.encode(out);
+                stats.encode(out);
+            }
+        }
+    }
+
+    fn decode(input: &mut &[u8]) -> Option<Self> {
+        match u8::decode(input)? {
+            0 => Some(ServerMessage::Welcome {
+                player: Wire::decode(input)?,
+            }),
+            1 => Some(ServerMessage::Snapshot(Box::new(Wire::decode(input)?))),
+            2 => Some(ServerMessage::GameOver(Wire::decode(input)?)),
+            _ => None,
+        }
+    }
+}
+
+/// Everything the clients need to draw one tick of the run
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    pub tick: u32,
+    /// last input of each player that the server has simulated
+    pub acks: [u32; 2],
+    pub game_t: f32,
+    pub level: usize,
+    pub xp: usize,
+    pub next_level_xp: usize,
+    pub biome: u8,
+    pub view_scale: f32,
+    pub ships: [ShipState; 2],
+    pub asteroids: Vec<AsteroidState>,
+    pub bullets: Vec<BulletState>,
+    pub rockets: Vec<RocketState>,
+    pub black_holes: Vec<BlackHoleState>,
+    pub white_holes: Vec<WhiteHoleState>,
+    pub wormholes: Vec<WormholeState>,
+    pub xp_orbs: Vec<OrbState>,
+    pub radiation: Vec<RadiationState>,
+    pub explosions: Vec<ExplosionState>,
+    pub damage_numbers: Vec<DamageNumberState>,
+    pub level_up: Option<LevelUpState>,
+    /// label and count of each picked upgrade
+    pub inventory: Vec<(String, usize)>,
+}
+
+wire_struct!(Snapshot {
+    tick,
+    acks,
+    game_t,
+    level,
+    xp,
+    next_level_xp,
+    biome,
+    view_scale,
+    ships,
+    asteroids,
+    bullets,
+    rockets,
+    black_holes,
+    white_holes,
+    wormholes,
+    xp_orbs,
+    radiation,
+    explosions,
+    damage_numbers,
+    level_up,
+    inventory,
+});
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShipState {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub rot: f32,
+    pub t: f32,
+    pub shields: f32,
+    pub rocket_stockpile: usize,
+    pub invulnerable_until: f32,
+    pub last_singularity: f32,
+    /// whether the wreck was swallowed and how far its revival is
+    pub down: Option<(bool, f32)>,
+}
+
+wire_struct!(ShipState {
+    pos,
+    vel,
+    rot,
+    t,
+    shields,
+    rocket_stockpile,
+    invulnerable_until,
+    last_singularity,
+    down,
+});
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AsteroidState {
+    pub id: u32,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub rot: f32,
+    pub size: f32,
+    pub material: u8,
+    pub hostile: bool,
+    /// only sent to a client the first time it sees the asteroid
+    pub shape: Option<ShapeState>,
+}
+
+wire_struct!(AsteroidState {
+    id,
+    pos,
+    vel,
+    rot,
+    size,
+    material,
+    hostile,
+    shape,
+});
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShapeState {
+    pub corners: Vec<Vec2>,
+    pub craters: Vec<(Vec2, f32)>,
+}
+
+wire_struct!(ShapeState { corners, craters });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BulletState {
+    pub id: u32,
+    pub pos: Vec2,
+    pub vel: Vec2,
+}
+
+wire_struct!(BulletState { id, pos, vel });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RocketState {
+    pub id: u32,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub rot: f32,
+}
+
+wire_struct!(RocketState { id, pos, vel, rot });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BlackHoleState {
+    pub id: u32,
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub mass: f32,
+    pub expires_at: Option<f32>,
+}
+
+wire_struct!(BlackHoleState {
+    id,
+    pos,
+    vel,
+    mass,
+    expires_at,
+});
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WhiteHoleState {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub size: f32,
+}
+
+wire_struct!(WhiteHoleState { pos, vel, size });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WormholeState {
+    pub ends: [Vec2; 2],
+    /// red, green and blue
+    pub color: [f32; 3],
+}
+
+wire_struct!(WormholeState { ends, color });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OrbState {
+    pub pos: Vec2,
+    pub vel: Vec2,
+}
+
+wire_struct!(OrbState { pos, vel });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RadiationState {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub damage: f32,
+    pub at: f32,
+}
+
+wire_struct!(RadiationState {
+    pos,
+    vel,
+    damage,
+    at,
+});
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExplosionState {
+    pub pos: Vec2,
+    pub radius: f32,
+    pub at: f32,
+}
+
+wire_struct!(ExplosionState { pos, radius, at });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DamageNumberState {
+    pub pos: Vec2,
+    pub amount: f32,
+    pub at: f32,
+}
+
+wire_struct!(DamageNumberState { pos, amount, at });
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelUpState {
+    /// labels of the upgrades to choose from
+    pub choices: Vec<String>,
+    pub selected: usize,
+    pub picker: usize,
+}
+
+wire_struct!(LevelUpState {
+    choices,
+    selected,
+    picker,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshots_survive_the_trip() {
+        let snapshot = Snapshot {
+            tick: 42,
+            acks: [7, 9],
+            game_t: 12.5,
+            level: 3,
+            biome: 4,
+            ships: [
+                ShipState {
+                    pos: Vec2::new(1., -2.),
+                    down: Some((true, 3.5)),
+                    ..ShipState::default()
+                },
+                ShipState::default(),
+            ],
+            asteroids: vec![AsteroidState {
+                id: 5,
+                material: 2,
+                shape: Some(ShapeState {
+                    corners: vec![Vec2::ZERO, Vec2::X, Vec2::Y],
+                    craters: vec![(Vec2::ONE, 0.5)],
+                }),
+                ..AsteroidState::default()
+            }],
+            level_up: Some(LevelUpState {
+                choices: vec!["SHD".to_string(), "BRK".to_string()],
+                selected: 1,
+                picker: 1,
+            }),
+            inventory: vec![("RCK".to_string(), 2)],
+            ..Snapshot::default()
+        };
+
+        let bytes = ServerMessage::Snapshot(Box::new(snapshot.clone())).to_bytes();
+        match ServerMessage::from_bytes(&bytes) {
+            Some(ServerMessage::Snapshot(decoded)) => assert_eq!(*decoded, snapshot),
+            _ => panic!("snapshot did not decode"),
+        }
+
+        assert!(ServerMessage::from_bytes(&bytes[..bytes.len() - 1]).is_none());
+    }
+
+    #[test]
+    fn inputs_survive_the_trip() {
+        let input = ShipInput {
+            up: true,
+            shoot: true,
+            confirm: true,
+            ..ShipInput::default()
+        };
+        let bytes = ClientMessage::Input { seq: 3, input }.to_bytes();
+        assert_eq!(bytes.len(), 6);
+        match ClientMessage::from_bytes(&bytes) {
+            Some(ClientMessage::Input {
+                seq,
+                input: decoded,
+            }) => {
+                assert_eq!(seq, 3);
+                assert_eq!(decoded, input);
+            }
+            None => panic!("input did not decode"),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    io,
+    net::{SocketAddr, TcpListener, TcpStream},
+    thread,
+    time::{Duration, Instant},
+};
+
+use macroquad::prelude::*;
+
+use crate::states::{
+    main_state::{MainState, ShipInput},
+    run_stats::RunStats,
+};
+
+use super::{
+    protocol::{ClientMessage, ServerMessage, Snapshot, Wire, SNAPSHOT_INTERVAL, TICK_RATE},
+    Connection,
+};
+
+const PLAYERS: usize = 2;
+/// How often the lobby looks for new players
+const LOBBY_POLL: Duration = Duration::from_millis(50);
+/// Inputs beyond this many ticks ahead of the simulation are dropped, so that a client whose
+/// clock runs fast doesn't fall further and further behind
+const MAX_QUEUED_INPUTS: usize = 10;
+
+struct Player {
+    connection: Connection,
+    /// inputs that arrived but were not simulated yet
+    inputs: VecDeque<(u32, ShipInput)>,
+    /// input of the last simulated tick, held until the next one arrives
+    input: ShipInput,
+    /// sequence number of the last simulated input
+    ack: u32,
+    /// asteroids whose shape the client already has
+    known_shapes: HashSet<u32>,
+}
+
+impl Player {
+    fn join(stream: TcpStream, idx: usize) -> io::Result<Self> {
+        let mut player = Self {
+            connection: Connection::accept(stream)?,
+            inputs: VecDeque::new(),
+            input: ShipInput::default(),
+            ack: 0,
+            known_shapes: HashSet::new(),
+        };
+        player.send(&ServerMessage::Welcome { player: idx })?;
+        Ok(player)
+    }
+
+    fn send(&mut self, message: &ServerMessage) -> io::Result<()> {
+        self.connection.send(message.to_bytes())
+    }
+
+    /// Queues the inputs that arrived, fails when the client is gone
+    fn receive(&mut self) -> io::Result<()> {
+        while let Some(bytes) = self.connection.receive()? {
+            match ClientMessage::from_bytes(&bytes) {
+                Some(ClientMessage::Input { seq, input }) => self.inputs.push_back((seq, input)),
+                None => warn!("Ignoring a malformed message"),
+            }
+        }
+        while self.inputs.len() > MAX_QUEUED_INPUTS {
+            self.inputs.pop_front();
+        }
+        Ok(())
+    }
+
+    /// The input for the next tick, the last one again if the client lags behind
+    fn next_input(&mut self) -> ShipInput {
+        if let Some((seq, input)) = self.inputs.pop_front() {
+            self.ack = seq;
+            self.input = input;
+        }
+        self.input
+    }
+
+    /// Sends the snapshot without the shapes the client already knows
+    fn send_snapshot(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let mut snapshot = snapshot.clone();
+        let mut known = HashSet::new();
+        for asteroid in snapshot.asteroids.iter_mut() {
+            if self.known_shapes.contains(&asteroid.id) {
+                asteroid.shape = None;
+            }
+            known.insert(asteroid.id);
+        }
+        self.known_shapes = known;
+        self.send(&ServerMessage::Snapshot(Box::new(snapshot)))
+    }
+}
+
+/// Runs co-op for two clients, one run after the other
+pub struct Server {
+    listener: TcpListener,
+}
+
+impl Server {
+    pub fn bind(address: &str) -> io::Result<Self> {
+        let listener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self { listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    pub fn run(&self) -> io::Result<()> {
+        loop {
+            let players = self.lobby()?;
+            info!("Both players are here, starting a run");
+            self.play(players);
+        }
+    }
+
+    /// Waits until both players are connected
+    fn lobby(&self) -> io::Result<Vec<Player>> {
+        let mut players: Vec<Player> = Vec::new();
+        while players.len() < PLAYERS {
+            match self.listener.accept() {
+                Ok((stream, addr)) => match Player::join(stream, players.len()) {
+                    Ok(player) => {
+                        info!("Player {} joined from {}", players.len() + 1, addr);
+                        players.push(player);
+                    }
+                    Err(err) => warn!("Failed to greet {}: {}", addr, err),
+                },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => thread::sleep(LOBBY_POLL),
+                Err(err) => return Err(err),
+            }
+
+            // whoever leaves frees their seat, the others move up
+            let waiting = players.len();
+            players.retain_mut(|player| player.receive().is_ok());
+            if players.len() < waiting {
+                info!("A player left the lobby");
+                for (idx, player) in players.iter_mut().enumerate() {
+                    player.send(&ServerMessage::Welcome { player: idx }).ok();
+                }
+            }
+        }
+
+        // what was pressed while waiting doesn't count
+        for player in players.iter_mut() {
+            player.inputs.clear();
+        }
+        Ok(players)
+    }
+
+    /// Simulates a run at a fixed rate until it is lost or a player leaves
+    fn play(&self, mut players: Vec<Player>) {
+        let mut state = MainState::online();
+        let tick_time = Duration::from_secs_f32(1. / TICK_RATE);
+        let mut next_tick = Instant::now();
+        let mut tick: u32 = 0;
+
+        let stats = loop {
+            if let Some(idx) = players.iter_mut().position(|p| p.receive().is_err()) {
+                info!("Player {} left, the run is over", idx + 1);
+                break state.run_stats();
+            }
+            for (idx, player) in players.iter_mut().enumerate() {
+                state.set_input(idx, player.next_input());
+            }
+
+            if let Some(stats) = state.simulate(1. / TICK_RATE) {
+                info!("The run is lost");
+                break stats;
+            }
+
+            if tick.is_multiple_of(SNAPSHOT_INTERVAL) {
+                let mut snapshot = state.snapshot();
+                snapshot.tick = tick;
+                snapshot.acks = [players[0].ack, players[1].ack];
+                for player in players.iter_mut() {
+                    // a client that is gone is noticed when receiving
+                    player.send_snapshot(&snapshot).ok();
+                }
+            }
+            tick += 1;
+
+            next_tick += tick_time;
+            let now = Instant::now();
+            if next_tick > now {
+                thread::sleep(next_tick - now);
+            } else if now - next_tick > tick_time * 10 {
+                // don't rush to catch up after a long stall
+                next_tick = now;
+            }
+        };
+
+        send_game_over(&mut players, stats);
+    }
+}
+
+fn send_game_over(players: &mut [Player], stats: RunStats) {
+    let message = ServerMessage::GameOver(stats);
+    for player in players.iter_mut() {
+        player.send(&message).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::net::protocol::ClientMessage;
+
+    use super::*;
+
+    fn connect(addr: SocketAddr) -> Connection {
+        Connection::connect(&addr.to_string()).unwrap()
+    }
+
+    /// Waits for the next message from the server
+    fn receive(connection: &mut Connection) -> ServerMessage {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            if let Some(bytes) = connection.receive().unwrap() {
+                return ServerMessage::from_bytes(&bytes).unwrap();
+            }
+            assert!(Instant::now() < deadline, "no message from the server");
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn two_clients_play_on_localhost() {
+        let (addr_tx, addr_rx) = mpsc::channel();
+        // the run isn't `Send`, so the server is made on its own thread
+        thread::spawn(move || {
+            let server = Server::bind("127.0.0.1:0").unwrap();
+            addr_tx.send(server.local_addr().unwrap()).unwrap();
+            server.run().unwrap();
+        });
+        let addr = addr_rx.recv().unwrap();
+
+        let mut clients = [connect(addr), connect(addr)];
+        for (idx, client) in clients.iter_mut().enumerate() {
+            match receive(client) {
+                ServerMessage::Welcome { player } => assert_eq!(player, idx),
+                _ => panic!("expected a welcome"),
+            }
+        }
+
+        let thrust = ShipInput {
+            up: true,
+            ..ShipInput::default()
+        };
+        for seq in 1..=5 {
+            let message = ClientMessage::Input { seq, input: thrust };
+            clients[0].send(message.to_bytes()).unwrap();
+        }
+
+        // the first snapshot brings every shape, the later ones only new asteroids'
+        let mut shapes_sent = false;
+        let deadline = Instant::now() + Duration::from_secs(5);
+        loop {
+            match receive(&mut clients[0]) {
+                ServerMessage::Snapshot(snapshot) => {
+                    let with_shape = snapshot.asteroids.iter().filter(|a| a.shape.is_some());
+                    if !shapes_sent {
+                        assert_eq!(with_shape.count(), snapshot.asteroids.len());
+                        shapes_sent = true;
+                    }
+                    if snapshot.acks[0] == 5 {
+                        assert!(snapshot.ships[0].vel.length() > 0.);
+                        break;
+                    }
+                }
+                _ => panic!("expected a snapshot"),
+            }
+            assert!(Instant::now() < deadline, "the inputs were never simulated");
+        }
+
+        // when player two leaves, player one is told that the run is over
+        let [mut one, two] = clients;
+        drop(two);
+        loop {
+            match receive(&mut one) {
+                ServerMessage::GameOver(stats) => {
+                    assert!(stats.time > 0.);
+                    break;
+                }
+                ServerMessage::Snapshot(_) => {}
+                _ => panic!("expected the end of the run"),
+            }
+        }
+    }
+}
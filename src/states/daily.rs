@@ -0,0 +1,189 @@
+use macroquad::logging::warn;
+
+use crate::utils::SeededRng;
+
+use super::mutators::Mutator;
+
+#[cfg(not(target_arch = "wasm32"))]
+const SCORES_FILE: &str = "daily_scores.txt";
+const MUTATORS_PER_DAY: usize = 2;
+/// Previous days listed on the results screen
+const HISTORY_DAYS: usize = 3;
+
+/// Days since the unix epoch (UTC)
+pub fn today() -> u32 {
+    (macroquad::miniquad::date::now() / 86400.) as u32
+}
+
+/// Formats a day since the unix epoch as `yyyy-mm-dd`
+pub fn format_day(day: u32) -> String {
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = day as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!("{:04}-{:02}-{:02}", y, m, d)
+}
+
+/// Seed and mutators of a day, the same for every player
+pub struct DailyChallenge {
+    pub day: u32,
+    pub seed: u64,
+    pub mutators: Vec<Mutator>,
+}
+
+impl DailyChallenge {
+    pub fn for_day(day: u32) -> Self {
+        let mut rng = SeededRng::new(day as u64);
+        let seed = rng.next_u64();
+
+        let mut candidates = Mutator::ALL.to_vec();
+        let mut mutators = Vec::new();
+        for _ in 0..MUTATORS_PER_DAY {
+            mutators.push(candidates.remove(rng.gen_index(0, candidates.len())));
+        }
+
+        Self {
+            day,
+            seed,
+            mutators,
+        }
+    }
+
+    pub fn today() -> Self {
+        Self::for_day(today())
+    }
+
+    pub fn description(&self) -> String {
+        let names: Vec<_> = self.mutators.iter().map(|m| m.name()).collect();
+        format!("{}: {}", format_day(self.day), names.join(", "))
+    }
+}
+
+/// The scored attempt of each day, kept on disk. The web build has no storage yet, there the
+/// scores are forgotten when the page is closed.
+#[derive(Default)]
+pub struct DailyScores {
+    scores: Vec<(u32, usize)>,
+}
+
+impl DailyScores {
+    pub fn load() -> Self {
+        let scores = read_store()
+            .lines()
+            .filter_map(|line| {
+                let (day, score) = line.split_once(' ')?;
+                Some((day.parse().ok()?, score.parse().ok()?))
+            })
+            .collect();
+        Self { scores }
+    }
+
+    pub fn get(&self, day: u32) -> Option<usize> {
+        self.scores.iter().find(|(d, _)| *d == day).map(|(_, s)| *s)
+    }
+
+    /// Records the score of a day, only the first attempt of each day counts
+    pub fn record(&mut self, day: u32, score: usize) -> bool {
+        if self.get(day).is_some() {
+            return false;
+        }
+        self.scores.push((day, score));
+        let store: String = self
+            .scores
+            .iter()
+            .map(|(day, score)| format!("{} {}\n", day, score))
+            .collect();
+        write_store(&store);
+        true
+    }
+
+    /// Results of a day compared to the days before
+    pub fn comparison(&self, day: u32) -> Vec<String> {
+        let mut lines = Vec::new();
+        let Some(score) = self.get(day) else {
+            return lines;
+        };
+
+        let mut previous: Vec<_> = self.scores.iter().filter(|(d, _)| *d < day).collect();
+        previous.sort_by_key(|(d, _)| std::cmp::Reverse(*d));
+        if let Some(best) = previous.iter().map(|(_, s)| *s).max() {
+            let rank = previous.iter().filter(|(_, s)| *s > score).count() + 1;
+            lines.push(format!(
+                "Rank {} of {} days, best previous score: {}",
+                rank,
+                previous.len() + 1,
+                best
+            ));
+        }
+        for (d, s) in previous.iter().take(HISTORY_DAYS) {
+            lines.push(format!("{}: {}", format_day(*d), s));
+        }
+        lines
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_store() -> String {
+    std::fs::read_to_string(SCORES_FILE).unwrap_or_default()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_store(store: &str) {
+    if let Err(err) = std::fs::write(SCORES_FILE, store) {
+        warn!("Could not save daily scores: {}", err);
+    }
+}
+
+// there is no file system on the web and no local storage binding, scores only last for the
+// session
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static STORE: std::cell::RefCell<String> = std::cell::RefCell::new(String::new());
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_store() -> String {
+    STORE.with(|store| store.borrow().clone())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_store(store: &str) {
+    STORE.with(|s| *s.borrow_mut() = store.to_string());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn challenges_are_stable() {
+        let day = 20000;
+        let a = DailyChallenge::for_day(day);
+        let b = DailyChallenge::for_day(day);
+        assert_eq!(a.seed, b.seed);
+        assert_eq!(a.mutators, b.mutators);
+        assert_ne!(a.seed, DailyChallenge::for_day(day + 1).seed);
+    }
+
+    #[test]
+    fn challenges_have_distinct_mutators() {
+        for day in 20000..20100 {
+            let mutators = DailyChallenge::for_day(day).mutators;
+            assert_eq!(mutators.len(), MUTATORS_PER_DAY);
+            assert_ne!(mutators[0], mutators[1]);
+        }
+    }
+
+    #[test]
+    fn days_are_formatted_as_dates() {
+        assert_eq!(format_day(0), "1970-01-01");
+        assert_eq!(format_day(11016), "2000-02-29");
+        assert_eq!(format_day(20744), "2026-10-18");
+    }
+}
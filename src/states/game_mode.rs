@@ -0,0 +1,101 @@
+/// What decides a versus match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersusGoal {
+    /// the last ship with lives left wins
+    Survival,
+    /// the first player to destroy the other often enough wins
+    Kills,
+}
+
+/// The different ways a run can be played, chosen in the menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum GameMode {
+    /// Endless open world full of black holes
+    #[default]
+    Survivor,
+    /// The open world, but won by surviving until the time runs out
+    Timed { minutes: u32 },
+    /// The same world and modifiers for everyone, derived from the date
+    Daily,
+    /// Two ships on one screen, sharing XP
+    Coop,
+    /// Two ships on a split screen, fighting each other in the same world
+    Versus(VersusGoal),
+    /// Co-op with the second player on another computer, a dedicated server runs the world
+    Online,
+    /// A single wrapping screen that has to be cleared wave by wave
+    Classic,
+    /// Practice in the open world with spawn tools, never ends and is not scored
+    Sandbox,
+}
+
+impl GameMode {
+    const ALL: &'static [GameMode] = &[
+        GameMode::Survivor,
+        GameMode::Timed { minutes: 15 },
+        GameMode::Timed { minutes: 30 },
+        GameMode::Daily,
+        GameMode::Coop,
+        // the web build can't connect to the server yet
+        #[cfg(not(target_arch = "wasm32"))]
+        GameMode::Online,
+        GameMode::Versus(VersusGoal::Survival),
+        GameMode::Versus(VersusGoal::Kills),
+        GameMode::Classic,
+        GameMode::Sandbox,
+    ];
+
+    pub fn name(self) -> String {
+        match self {
+            GameMode::Survivor => "Survivor".to_string(),
+            GameMode::Timed { minutes } => format!("Timed {} min", minutes),
+            GameMode::Daily => "Daily challenge".to_string(),
+            GameMode::Coop => "Co-op".to_string(),
+            GameMode::Online => "Online co-op".to_string(),
+            GameMode::Versus(VersusGoal::Survival) => "Versus (survival)".to_string(),
+            GameMode::Versus(VersusGoal::Kills) => "Versus (kills)".to_string(),
+            GameMode::Classic => "Classic".to_string(),
+            GameMode::Sandbox => "Sandbox".to_string(),
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let idx = Self::ALL.iter().position(|&mode| mode == self).unwrap();
+        Self::ALL[(idx + 1) % Self::ALL.len()]
+    }
+
+    pub fn prev(self) -> Self {
+        let idx = Self::ALL.iter().position(|&mode| mode == self).unwrap();
+        Self::ALL[(idx + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+
+    /// Whether the ship flies through the endless, generated world
+    pub fn open_world(self) -> bool {
+        match self {
+            GameMode::Survivor
+            | GameMode::Timed { .. }
+            | GameMode::Daily
+            | GameMode::Coop
+            | GameMode::Online
+            | GameMode::Versus(_)
+            | GameMode::Sandbox => true,
+            GameMode::Classic => false,
+        }
+    }
+
+    /// Whether a second ship joins the run
+    pub fn two_players(self) -> bool {
+        matches!(
+            self,
+            GameMode::Coop | GameMode::Online | GameMode::Versus(_)
+        )
+    }
+
+    /// World time after which a run is won, `None` for runs without a time limit
+    pub fn run_length(self) -> Option<f32> {
+        match self {
+            GameMode::Timed { minutes } => Some(minutes as f32 * 60.),
+            _ => None,
+        }
+    }
+}
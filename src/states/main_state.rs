@@ -8,13 +8,50 @@ use std::{
 
 use macroquad::prelude::*;
 
-use crate::{utils::draw_centered_text, GameState};
+use crate::{
+    utils::{draw_centered_text, random_seed, SeededRng},
+    GameState,
+};
+
+use self::{
+    anomalies::{WhiteHole, Wormhole},
+    coop::Downed,
+    gravity::{Body, ForceLaw},
+    maneuvers::Maneuvers,
+    radar::RadarDetail,
+    world::{Biome, ChunkCoord, World},
+};
+
+use super::{
+    daily::DailyChallenge,
+    game_mode::GameMode,
+    menu_state::MenuState,
+    mutators::{Difficulty, Mutator},
+    run_stats::RunStats,
+};
 
-use super::menu_state::MenuState;
+mod anomalies;
+mod classic;
+mod coop;
+mod gravity;
+mod maneuvers;
+#[cfg(not(target_arch = "wasm32"))]
+mod online;
+mod radar;
+mod sandbox;
+mod threats;
+mod timed;
+mod trajectory;
+mod versus;
+mod world;
+
+/// Part of the world that is always visible, the camera zooms so that it fits the window
+const VIEW_SIZE: Vec2 = Vec2::new(800., 600.);
 
 const SHIP_HEIGHT: f32 = 25.;
 const SHIP_BASE: f32 = 22.;
 const ROCKET_SIZE: f32 = 8.;
+const RESPAWN_INVULNERABILITY: f32 = 2.; // sec
 
 const BULLET_LIFETIME: f32 = 1.5; // sec
 const ROCKET_LIFETIME: f32 = 4.0; // sec
@@ -23,17 +60,212 @@ const ASTEROID_DENSITY: usize = 4;
 
 const SHIP_ROTATION_SPEED: f32 = 4.; // deg/frame
 
+const ASTEROID_HP_PER_SIZE: f32 = 0.025;
+const IRON_HP_FACTOR: f32 = 3.;
+const EXPLOSION_DAMAGE: f32 = 3.;
+const DAMAGE_NUMBER_DURATION: f32 = 0.8; // sec
+const HIT_IMPULSE_PER_DAMAGE: f32 = 40.; // momentum transferred per damage and unit of hit speed
+const VOLATILE_EXPLOSION_FACTOR: f32 = 3.; // explosion radius relative to asteroid size
+const EXPLOSION_DURATION: f32 = 0.4; // sec
+const CRYSTAL_XP: usize = 3;
+const MAGNETIC_RANGE: f32 = 600.;
+const XP_ORB_PICKUP_RANGE: f32 = 150.;
+
+/// Time slows down around black holes as if their horizon was this many times their size
+const TIME_DILATION_SCALE: f32 = 3.;
+const MIN_TIME_DILATION: f32 = 0.2;
+
+/// Radius of the horizon in px per unit of mass, it grows linearly with the mass like a
+/// Schwarzschild radius. The pull only depends on the mass.
+const HORIZON_PER_MASS: f32 = 0.8;
+/// Hawking radiation makes black holes lose `HAWKING_RATE / mass²` mass per second,
+/// so their mass³ shrinks by `3 * HAWKING_RATE` per second and small ones vanish quickly
+const HAWKING_RATE: f32 = 20.;
+const BLACK_HOLE_MIN_SIZE: f32 = 2.;
+const BLACK_HOLE_COLLAPSE_SIZE: f32 = 300.;
+const BLACK_HOLE_FEED: f32 = 1. / 2000.; // mass gained per swallowed mass
+const RADIATION_PER_MASS: f32 = 4.; // particles emitted per evaporated mass
+const RADIATION_LIFETIME: f32 = 1.; // sec
+const RADIATION_SPEED: f32 = 4.;
+const RADIATION_DAMAGE: f32 = 0.2;
+const COLLAPSE_RADIATION: usize = 120;
+const COLLAPSE_RADIATION_DAMAGE: f32 = 2.;
+
+const NEBULA_VISIBILITY: f32 = 250.;
+const NEBULA_MAX_SPEED: f32 = 3.;
+const ION_STORM_SHIELD_DRAIN: f32 = 1. / 30.; // shields/sec
+
+// player made singularities
+const SINGULARITY_RANGE: f32 = 250.; // px in front of the ship
+const SINGULARITY_MAX_MASS: f32 = 60.;
+
 trait BlackHoleEffected {
     fn pos(&self) -> Vec2;
+    fn set_pos(&mut self, pos: Vec2);
     fn vel(&mut self) -> &mut Vec2;
     fn radius(&self) -> f32;
+    /// mass a black hole gains by swallowing the object
+    fn mass(&self) -> f32;
     fn collide(&mut self);
 }
 
+/// Keys a player flies with
+#[derive(Debug, Clone, Copy)]
+struct Controls {
+    up: KeyCode,
+    down: KeyCode,
+    left: KeyCode,
+    right: KeyCode,
+    shoot: KeyCode,
+    rocket: KeyCode,
+    singularity: KeyCode,
+    /// takes the selected upgrade when leveling up
+    confirm: KeyCode,
+}
+
+const SOLO_CONTROLS: Controls = Controls {
+    up: KeyCode::Up,
+    down: KeyCode::Down,
+    left: KeyCode::Left,
+    right: KeyCode::Right,
+    shoot: KeyCode::Space,
+    rocket: KeyCode::LeftAlt,
+    singularity: KeyCode::LeftShift,
+    confirm: KeyCode::Enter,
+};
+
+impl Controls {
+    fn read(&self) -> ShipInput {
+        ShipInput {
+            up: is_key_down(self.up),
+            down: is_key_down(self.down),
+            left: is_key_down(self.left),
+            right: is_key_down(self.right),
+            shoot: is_key_down(self.shoot),
+            rocket: is_key_down(self.rocket),
+            singularity: is_key_down(self.singularity),
+            confirm: is_key_down(self.confirm),
+        }
+    }
+}
+
+/// Buttons a player holds in a frame, read from their controls or received by the online server
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ShipInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub shoot: bool,
+    pub rocket: bool,
+    pub singularity: bool,
+    pub confirm: bool,
+}
+
+impl ShipInput {
+    /// Buttons that are held now but were not held in the `last` frame
+    fn pressed(self, last: ShipInput) -> ShipInput {
+        ShipInput {
+            up: self.up && !last.up,
+            down: self.down && !last.down,
+            left: self.left && !last.left,
+            right: self.right && !last.right,
+            shoot: self.shoot && !last.shoot,
+            rocket: self.rocket && !last.rocket,
+            singularity: self.singularity && !last.singularity,
+            confirm: self.confirm && !last.confirm,
+        }
+    }
+}
+
 struct Ship {
     pos: Vec2,
     rot: f32,
     vel: Vec2,
+    controls: Controls,
+    input: ShipInput,
+    /// input of the frame before, to tell presses from held buttons
+    last_input: ShipInput,
+    /// proper time of the ship, which passes slower close to black holes. The cooldowns and
+    /// shields of the ship run on it.
+    t: f32,
+    last_bullet_shot: f32,
+    last_rocket_shot: f32,
+    last_singularity: f32,
+    rocket_side: RocketSide,
+    invulnerable_until: f32,
+    /// whether the ship touched an asteroid in the last frame
+    colliding: bool,
+    /// set while the wreck of the ship waits for its partner to revive it
+    down: Option<Downed>,
+    shields: f32,
+    rocket_stockpile: usize,
+    /// opponents destroyed in versus
+    kills: usize,
+    /// times the ship was destroyed in versus
+    deaths: usize,
+}
+
+impl Ship {
+    fn new(pos: Vec2, controls: Controls) -> Self {
+        Self {
+            pos,
+            rot: 0.,
+            vel: Vec2::ZERO,
+            controls,
+            input: ShipInput::default(),
+            last_input: ShipInput::default(),
+            t: 0.,
+            last_bullet_shot: 0.,
+            last_rocket_shot: 0.,
+            last_singularity: 0.,
+            rocket_side: RocketSide::Right,
+            invulnerable_until: 0.,
+            colliding: false,
+            down: None,
+            shields: 0.,
+            rocket_stockpile: 2,
+            kills: 0,
+            deaths: 0,
+        }
+    }
+
+    fn set_input(&mut self, input: ShipInput) {
+        self.last_input = self.input;
+        self.input = input;
+    }
+
+    /// Thrusts, brakes and steers by the input, then moves the ship by `dt` frames
+    fn fly(&mut self, biome: Biome, brakes: bool, dt: f32) {
+        let acc = if self.input.up {
+            vec_from_rot(self.rot.to_radians()) / 3.
+        } else if self.input.down && brakes {
+            -self.vel / 20. // Break
+        } else if biome == Biome::Nebula {
+            -self.vel / 100. // Nebula drag
+        } else {
+            -self.vel / 1000. // Friction
+        };
+
+        // Steer
+        if self.input.right {
+            self.rot += SHIP_ROTATION_SPEED * dt;
+        } else if self.input.left {
+            self.rot -= SHIP_ROTATION_SPEED * dt;
+        }
+
+        // Euler integration
+        let max_speed = if biome == Biome::Nebula {
+            NEBULA_MAX_SPEED
+        } else {
+            5.
+        };
+        self.vel += acc * dt;
+        if self.vel.length() > max_speed {
+            self.vel = self.vel.normalize() * max_speed;
+        }
+        self.pos += self.vel * dt;
+    }
 }
 
 impl BlackHoleEffected for Ship {
@@ -41,6 +273,10 @@ impl BlackHoleEffected for Ship {
         self.pos
     }
 
+    fn set_pos(&mut self, pos: Vec2) {
+        self.pos = pos;
+    }
+
     fn vel(&mut self) -> &mut Vec2 {
         &mut self.vel
     }
@@ -49,6 +285,10 @@ impl BlackHoleEffected for Ship {
         SHIP_HEIGHT / 3.
     }
 
+    fn mass(&self) -> f32 {
+        100.
+    }
+
     fn collide(&mut self) {}
 }
 
@@ -57,6 +297,10 @@ struct Bullet {
     vel: Vec2,
     shot_at: f32,
     collided: bool,
+    /// index of the ship that fired it
+    owner: usize,
+    /// id the online server sends the bullet with, zero until it was first sent
+    net_id: u32,
 }
 
 impl BlackHoleEffected for Bullet {
@@ -64,6 +308,10 @@ impl BlackHoleEffected for Bullet {
         self.pos
     }
 
+    fn set_pos(&mut self, pos: Vec2) {
+        self.pos = pos;
+    }
+
     fn vel(&mut self) -> &mut Vec2 {
         &mut self.vel
     }
@@ -72,11 +320,77 @@ impl BlackHoleEffected for Bullet {
         2.
     }
 
+    fn mass(&self) -> f32 {
+        20.
+    }
+
     fn collide(&mut self) {
         self.collided = true;
     }
 }
 
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum AsteroidMaterial {
+    Rock,
+    Ice,
+    Iron,
+    Volatile,
+    Crystal,
+    Magnetic,
+}
+
+impl AsteroidMaterial {
+    /// Picks a material by the level's and biome's spawn weights, `roll` is a random number in `[0, 1)`
+    fn random(level: usize, biome: Biome, roll: f32) -> Self {
+        let level = level as f32;
+        let weights = [
+            (AsteroidMaterial::Rock, 60.),
+            (AsteroidMaterial::Ice, 10. + level),
+            (AsteroidMaterial::Iron, 2. * (level - 2.).max(0.)),
+            (AsteroidMaterial::Volatile, level),
+            (AsteroidMaterial::Crystal, 5.),
+            (AsteroidMaterial::Magnetic, 2. * (level - 5.).max(0.)),
+        ];
+
+        let weights = weights.map(|(m, w)| (m, w * biome.material_weight(m)));
+
+        let total: f32 = weights.iter().map(|(_, w)| w).sum();
+        let mut choice = roll * total;
+        for (material, weight) in weights {
+            if choice < weight {
+                return material;
+            }
+            choice -= weight;
+        }
+        AsteroidMaterial::Rock
+    }
+
+    fn color(self) -> Color {
+        match self {
+            AsteroidMaterial::Rock => BLACK,
+            AsteroidMaterial::Ice => BLUE,
+            AsteroidMaterial::Iron => DARKBROWN,
+            AsteroidMaterial::Volatile => RED,
+            AsteroidMaterial::Crystal => PURPLE,
+            AsteroidMaterial::Magnetic => DARKGREEN,
+        }
+    }
+
+    fn hp_factor(self) -> f32 {
+        match self {
+            AsteroidMaterial::Iron => IRON_HP_FACTOR,
+            _ => 1.,
+        }
+    }
+
+    fn fragments(self) -> usize {
+        match self {
+            AsteroidMaterial::Ice => 3,
+            _ => 2,
+        }
+    }
+}
+
 struct Asteroid {
     pos: Vec2,
     vel: Vec2,
@@ -85,7 +399,15 @@ struct Asteroid {
     size: f32,
     sides: u8,
     collided: bool,
-    shape_idx: usize,
+    shape: AsteroidShape,
+    material: AsteroidMaterial,
+    hp: f32,
+    /// chunk and index the asteroid was generated for, if it is part of the persistent world
+    origin: Option<(ChunkCoord, u32)>,
+    /// sent towards the ship
+    hostile: bool,
+    /// id the online server sends the asteroid with, zero until it was first sent
+    net_id: u32,
 }
 
 impl BlackHoleEffected for Asteroid {
@@ -93,6 +415,10 @@ impl BlackHoleEffected for Asteroid {
         self.pos
     }
 
+    fn set_pos(&mut self, pos: Vec2) {
+        self.pos = pos;
+    }
+
     fn vel(&mut self) -> &mut Vec2 {
         &mut self.vel
     }
@@ -101,26 +427,251 @@ impl BlackHoleEffected for Asteroid {
         self.size
     }
 
+    fn mass(&self) -> f32 {
+        self.shape.area * self.size * self.size
+    }
+
     fn collide(&mut self) {
         self.collided = true;
     }
 }
 
 impl Asteroid {
-    fn new(pos: Vec2, asteroid_shapes: &Vec<AsteroidShape>) -> Asteroid {
+    fn new(pos: Vec2, material: AsteroidMaterial) -> Asteroid {
+        Self::generate(pos, material, &mut SeededRng::new(random_seed()))
+    }
+
+    fn generate(pos: Vec2, material: AsteroidMaterial, rng: &mut SeededRng) -> Asteroid {
+        let size = VIEW_SIZE.min_element() / 10.;
         Asteroid {
             pos,
-            vel: Vec2::new(rand::gen_range(-1., 1.), rand::gen_range(-1., 1.)),
+            vel: Vec2::new(rng.gen_range(-1., 1.), rng.gen_range(-1., 1.)),
             rot: 0.,
-            rot_speed: rand::gen_range(-2., 2.),
-            size: screen_width().min(screen_height()) / 10.,
-            sides: rand::gen_range(3, 8),
+            rot_speed: rng.gen_range(-2., 2.),
+            size,
+            sides: rng.gen_index(3, 8) as u8,
             collided: false,
-            shape_idx: rand::gen_range(0, asteroid_shapes.len()),
+            shape: AsteroidShape::generate(rng.next_u64()),
+            material,
+            hp: Self::max_hp(size, material),
+            origin: None,
+            hostile: false,
+            net_id: 0,
+        }
+    }
+
+    fn max_hp(size: f32, material: AsteroidMaterial) -> f32 {
+        size * ASTEROID_HP_PER_SIZE * material.hp_factor()
+    }
+
+    /// Whether a circle of radius `margin` around `point` touches the asteroid's outline.
+    fn touches(&self, point: Vec2, margin: f32) -> bool {
+        let offset = point - self.pos;
+        if offset.length() > self.size + margin {
+            return false;
+        }
+
+        let local = Vec2::from_angle(-self.rot.to_radians()).rotate(offset) / self.size;
+        self.shape.contains(local) || self.shape.distance(local) * self.size < margin
+    }
+
+    /// Corners of the outline in world coordinates
+    fn outline(&self) -> impl Iterator<Item = Vec2> + '_ {
+        let rotation = Vec2::from_angle(self.rot.to_radians());
+        self.shape
+            .corners
+            .iter()
+            .map(move |&c| self.pos + rotation.rotate(c) * self.size)
+    }
+
+    /// How deep the corners of `other`'s outline reach into this asteroid's outline
+    fn penetration(&self, other: &Asteroid) -> f32 {
+        let inverse = Vec2::from_angle(-self.rot.to_radians());
+        other
+            .outline()
+            .map(|p| {
+                let local = inverse.rotate(p - self.pos) / self.size;
+                if self.shape.contains(local) {
+                    self.shape.distance(local) * self.size
+                } else {
+                    0.
+                }
+            })
+            .fold(0., f32::max)
+    }
+
+    /// Breaks the asteroid along the hit direction into fragments of its outline.
+    /// The fragments share the parent's mass and momentum, the kicks they get cancel out.
+    fn split(&self, hit_vel: Vec2) -> Vec<Asteroid> {
+        if self.sides <= 3 {
+            return Vec::new();
+        }
+
+        let hit_dir = hit_vel.try_normalize().unwrap_or(Vec2::X);
+        let rotation = Vec2::from_angle(self.rot.to_radians());
+        let pieces = self
+            .shape
+            .fragments(rotation, self.size, hit_dir, self.material.fragments());
+
+        let kick = rand::gen_range(1., 3.);
+        let masses: Vec<f32> = pieces
+            .iter()
+            .map(|(_, p, size)| p.area * size * size)
+            .collect();
+        let momentum: Vec2 = pieces
+            .iter()
+            .zip(masses.iter())
+            .fold(Vec2::ZERO, |acc, ((offset, _, _), mass)| {
+                acc + offset.normalize_or_zero() * kick * *mass
+            });
+        let drift = momentum / masses.iter().sum::<f32>();
+
+        pieces
+            .into_iter()
+            .map(|(offset, shape, size)| Asteroid {
+                pos: self.pos + offset,
+                vel: self.vel + offset.normalize_or_zero() * kick - drift,
+                rot: 0.,
+                rot_speed: rand::gen_range(-2., 2.),
+                size,
+                sides: self.sides - 1,
+                collided: false,
+                shape,
+                material: self.material,
+                hp: Self::max_hp(size, self.material),
+                origin: None,
+                hostile: self.hostile,
+                net_id: 0,
+            })
+            .collect()
+    }
+}
+
+/// Half of a leapfrog kick from the black holes. Together with the other half after the drift,
+/// light objects are integrated the same way as the black holes in `ForceLaw::step`.
+fn half_kick(
+    black_holes: &[BlackHole],
+    gravity: &ForceLaw,
+    dt: f32,
+    objs: &mut [impl BlackHoleEffected],
+) {
+    for obj in objs.iter_mut() {
+        let pos = obj.pos();
+        let pull = black_holes
+            .iter()
+            .fold(Vec2::ZERO, |acc, bh| acc + bh.pull_at(gravity, pos));
+        *obj.vel() += pull * dt / 2.;
+    }
+}
+
+/// Elastic collisions between all pairs of asteroids whose outlines overlap.
+fn collide_asteroids(asteroids: &mut [Asteroid]) {
+    for i in 0..asteroids.len() {
+        let (left, right) = asteroids.split_at_mut(i + 1);
+        let a = &mut left[i];
+        if a.collided {
+            continue;
+        }
+
+        for b in right.iter_mut() {
+            let dist_vec = b.pos - a.pos;
+            if b.collided || dist_vec.length() > a.size + b.size {
+                continue;
+            }
+            let overlap = a.penetration(b).max(b.penetration(a));
+            if overlap <= 0. {
+                continue;
+            }
+
+            let Some(n) = dist_vec.try_normalize() else {
+                continue;
+            };
+            let (ma, mb) = (a.mass(), b.mass());
+
+            // separate them so they don't get stuck in each other
+            a.pos -= n * overlap * mb / (ma + mb);
+            b.pos += n * overlap * ma / (ma + mb);
+
+            let approach_speed = (a.vel - b.vel).dot(n);
+            if approach_speed > 0. {
+                let impulse = 2. * approach_speed / (1. / ma + 1. / mb);
+                a.vel -= n * impulse / ma;
+                b.vel += n * impulse / mb;
+            }
         }
     }
 }
 
+struct XpOrb {
+    pos: Vec2,
+    vel: Vec2,
+    xp: usize,
+    collided: bool,
+}
+
+impl BlackHoleEffected for XpOrb {
+    fn pos(&self) -> Vec2 {
+        self.pos
+    }
+
+    fn set_pos(&mut self, pos: Vec2) {
+        self.pos = pos;
+    }
+
+    fn vel(&mut self) -> &mut Vec2 {
+        &mut self.vel
+    }
+
+    fn radius(&self) -> f32 {
+        4.
+    }
+
+    fn mass(&self) -> f32 {
+        5.
+    }
+
+    fn collide(&mut self) {
+        self.collided = true;
+    }
+}
+
+struct DamageNumber {
+    pos: Vec2,
+    amount: f32,
+    at: f32,
+}
+
+struct Radiation {
+    pos: Vec2,
+    vel: Vec2,
+    damage: f32,
+    at: f32,
+    collided: bool,
+}
+
+impl Radiation {
+    fn burst(pos: Vec2, radius: f32, count: usize, damage: f32, at: f32) -> Vec<Radiation> {
+        (0..count)
+            .map(|_| {
+                let dir = Vec2::from_angle(rand::gen_range(0., 2. * PI));
+                Radiation {
+                    pos: pos + dir * radius,
+                    vel: dir * RADIATION_SPEED * rand::gen_range(0.8, 1.5),
+                    damage,
+                    at,
+                    collided: false,
+                }
+            })
+            .collect()
+    }
+}
+
+struct Explosion {
+    pos: Vec2,
+    radius: f32,
+    at: f32,
+}
+
 struct Rocket {
     pos: Vec2,
     vel: Vec2,
@@ -128,6 +679,10 @@ struct Rocket {
     collided: bool,
     shot_at: f32,
     steer: bool,
+    /// index of the ship that fired it
+    owner: usize,
+    /// id the online server sends the rocket with, zero until it was first sent
+    net_id: u32,
 }
 
 impl BlackHoleEffected for Rocket {
@@ -135,6 +690,10 @@ impl BlackHoleEffected for Rocket {
         self.pos
     }
 
+    fn set_pos(&mut self, pos: Vec2) {
+        self.pos = pos;
+    }
+
     fn vel(&mut self) -> &mut Vec2 {
         &mut self.vel
     }
@@ -143,53 +702,105 @@ impl BlackHoleEffected for Rocket {
         5.
     }
 
+    fn mass(&self) -> f32 {
+        50.
+    }
+
     fn collide(&mut self) {
         self.collided = true;
     }
 }
 
 struct BlackHole {
-    pos: Cell<Vec2>,
-    vel: Cell<Vec2>,
+    id: u32,
+    body: Body,
+    /// radius of the event horizon, follows from the mass
     size: f32,
-    collided: Cell<bool>,
+    collided: bool,
+    /// radiation particles that are due to be emitted
+    radiation: f32,
+    /// game time at which a player made singularity vanishes, `None` for natural black holes
+    expires_at: Option<f32>,
+    /// the black hole of the final phase of a timed run, it can neither evaporate nor collapse
+    event_horizon: bool,
 }
 
 impl BlackHole {
+    fn new(id: u32, pos: Vec2, vel: Vec2, mass: f32) -> Self {
+        Self::from_body(id, Body { pos, vel, mass })
+    }
+
+    fn from_body(id: u32, body: Body) -> Self {
+        Self {
+            id,
+            size: body.mass * HORIZON_PER_MASS,
+            body,
+            collided: false,
+            radiation: 0.,
+            expires_at: None,
+            event_horizon: false,
+        }
+    }
+
+    fn player_made(&self) -> bool {
+        self.expires_at.is_some()
+    }
+
     fn pos(&self) -> Vec2 {
-        self.pos.get()
+        self.body.pos
     }
 
     fn vel(&self) -> Vec2 {
-        self.vel.get()
+        self.body.vel
+    }
+
+    fn mass(&self) -> f32 {
+        self.body.mass
+    }
+
+    fn set_mass(&mut self, mass: f32) {
+        self.body.mass = mass.max(0.);
+        self.size = self.body.mass * HORIZON_PER_MASS;
+    }
+
+    /// Acceleration towards the black hole an object at `pos` experiences
+    fn pull_at(&self, gravity: &ForceLaw, pos: Vec2) -> Vec2 {
+        gravity.acceleration(self.pos(), self.mass(), pos)
     }
 }
 
 struct Upgrade {
+    label: &'static str,
     desc: Box<dyn Fn(&MainState) -> String>,
     effect: Box<dyn Fn(&mut MainState) -> bool>,
 }
 
 impl Upgrade {
     fn new(
+        label: &'static str,
         desc: impl Fn(&MainState) -> String + 'static,
         effect: impl Fn(&mut MainState) -> bool + 'static,
     ) -> Rc<Self> {
         Rc::new(Self {
+            label,
             desc: Box::new(desc),
             effect: Box::new(effect),
         })
     }
 
-    fn simple(desc: &str, effect: impl Fn(&mut MainState) -> bool + 'static) -> Rc<Self> {
+    fn simple(
+        label: &'static str,
+        desc: &str,
+        effect: impl Fn(&mut MainState) -> bool + 'static,
+    ) -> Rc<Self> {
         let desc = desc.to_string();
-        Self::new(move |_| desc.clone(), effect)
+        Self::new(label, move |_| desc.clone(), effect)
     }
 }
 
 fn make_upgrades() -> Vec<Rc<Upgrade>> {
     vec![
-        Upgrade::simple("Install brakes", |s| {
+        Upgrade::simple("BRK", "Install brakes", |s| {
             s.has_brakes = true;
             false
         }),
@@ -198,28 +809,68 @@ fn make_upgrades() -> Vec<Rc<Upgrade>> {
             let next_rockets2 = next_rockets.clone();
 
             Upgrade::new(
+                "MSL",
                 move |_| format!("+{} Missiles", next_rockets.get()),
                 move |s| {
                     let new_rockets = next_rockets2.get();
-                    s.rocket_stockpile += new_rockets;
+                    for ship in s.ships_mut() {
+                        ship.rocket_stockpile += new_rockets;
+                    }
                     next_rockets2.set(new_rockets + 5);
                     true
                 },
             )
         },
-        Upgrade::simple("-20% Missle reload time", |s| {
+        Upgrade::simple("MRL", "-20% Missle reload time", |s| {
             s.rocket_reload_time *= 0.8;
             s.rocket_reload_time > 0.05
         }),
-        Upgrade::simple("-20% Bullet reload time", |s| {
+        Upgrade::simple("BRL", "-20% Bullet reload time", |s| {
             s.bullet_reload_time *= 0.8;
             s.bullet_reload_time > 0.05
         }),
-        Upgrade::simple("+0.3 Missile production/s", |s| {
+        Upgrade::simple("BDM", "+25% Bullet damage", |s| {
+            s.bullet_damage *= 1.25;
+            true
+        }),
+        Upgrade::simple("MDM", "+25% Missile damage", |s| {
+            s.rocket_damage *= 1.25;
+            true
+        }),
+        Upgrade::simple("MPR", "+0.3 Missile production/s", |s| {
             s.rocket_production_per_sec += 0.3;
             true
         }),
+        Upgrade::simple("MTR", "Missile trajectory prediction", |s| {
+            s.missile_trajectories = true;
+            false
+        }),
+        Upgrade::simple("RDR", "+30% Radar range", |s| {
+            s.radar_range *= 1.3;
+            true
+        }),
+        Upgrade::new(
+            "RDD",
+            |s| format!("Radar: {}", s.radar_detail.next().name()),
+            |s| {
+                s.radar_detail = s.radar_detail.next();
+                s.radar_detail != s.radar_detail.next()
+            },
+        ),
+        Upgrade::simple("SGS", "+30% Singularity size", |s| {
+            s.singularity_mass *= 1.3;
+            s.singularity_mass < SINGULARITY_MAX_MASS
+        }),
+        Upgrade::simple("SGD", "+1s Singularity duration", |s| {
+            s.singularity_duration += 1.;
+            true
+        }),
+        Upgrade::simple("SGC", "-20% Singularity cooldown", |s| {
+            s.singularity_cooldown *= 0.8;
+            s.singularity_cooldown > 2.
+        }),
         Upgrade::new(
+            "SHD",
             |s| {
                 if s.shield_regeneration_per_sec == 0. {
                     "Install Shields".to_string()
@@ -228,8 +879,13 @@ fn make_upgrades() -> Vec<Rc<Upgrade>> {
                 }
             },
             |s| {
+                if !s.difficulty.shields {
+                    return false;
+                }
                 if s.shield_regeneration_per_sec == 0. {
-                    s.shields = 1.;
+                    for ship in s.ships_mut() {
+                        ship.shields = 1.;
+                    }
                     s.shield_regeneration_per_sec = 0.1 / 60.;
                 } else {
                     s.shield_regeneration_per_sec += 0.5 / 60.;
@@ -240,9 +896,26 @@ fn make_upgrades() -> Vec<Rc<Upgrade>> {
     ]
 }
 
+/// Upgrades the player has picked so far, in the order they were first chosen.
+#[derive(Default)]
+struct Inventory {
+    items: Vec<(Rc<Upgrade>, usize)>,
+}
+
+impl Inventory {
+    fn add(&mut self, upgrade: &Rc<Upgrade>) {
+        match self.items.iter_mut().find(|(u, _)| Rc::ptr_eq(u, upgrade)) {
+            Some((_, count)) => *count += 1,
+            None => self.items.push((upgrade.clone(), 1)),
+        }
+    }
+}
+
 struct LevelUp {
     selected: usize,
     upgrade_choices: Vec<Rc<Upgrade>>,
+    /// index of the ship whose player picks the upgrade
+    picker: usize,
 }
 
 impl LevelUp {
@@ -261,31 +934,11 @@ impl LevelUp {
         Self {
             selected: 0,
             upgrade_choices,
+            picker: 0,
         }
     }
 }
 
-/*fn wrap_around(v: &Vec2) -> Vec2 {
-    let mut vr = Vec2::new(v.x, v.y);
-    if vr.x > screen_width() {
-        vr.x = 0.;
-    }
-    if vr.x < 0. {
-        vr.x = screen_width()
-    }
-    if vr.y > screen_height() {
-        vr.y = 0.;
-    }
-    if vr.y < 0. {
-        vr.y = screen_height()
-    }
-    vr
-}*/
-
-fn rand_signum() -> f32 {
-    rand::gen_range::<f32>(-1., 1.).signum()
-}
-
 fn vec_from_rot(rot: f32) -> Vec2 {
     Vec2::new(rot.sin(), -rot.cos())
 }
@@ -306,214 +959,551 @@ impl RocketSide {
 }
 
 pub struct MainState {
+    mode: GameMode,
+    lives: usize,
+    /// current asteroid wave in classic mode
+    wave: usize,
+    next_wave_at: Option<f32>,
+    /// next scripted milestone of a timed run
+    next_milestone: usize,
+    /// id of the next black hole, unique within the run
+    next_black_hole_id: u32,
+    /// last id given to an asteroid, bullet or rocket sent by the online server
+    last_net_id: u32,
+    mutators: Vec<Mutator>,
+    difficulty: Difficulty,
     paused: bool,
+    /// world frames simulated per rendered frame, only changed in the sandbox. Below one the
+    /// frames are simulated in fractions.
+    time_scale: f32,
+    /// frames that are due to be simulated
+    pending_steps: f32,
+    /// upgrade the sandbox grants next, index into the available upgrades
+    sandbox_upgrade: usize,
+    /// sandbox ships bounce off everything and fly through black holes
+    invulnerable: bool,
     game_t: f32,
+    time_dilation: f32,
     ship: Ship,
-    invulnerable_until: f32,
-    colliding: bool,
-    last_asteroid_generate_pos: Vec2,
+    /// second ship in co-op
+    partner: Option<Ship>,
+    /// zoom relative to the normal one, so that both co-op ships fit on the screen
+    view_scale: f32,
+    world: World,
+    biome: Biome,
+    background: Color,
     generated_asteroids: usize,
     bullets: Vec<Bullet>,
-    last_bullet_shot: f32,
-    last_rocket_shot: f32,
     asteroids: Vec<Asteroid>,
     rockets: Vec<Rocket>,
-    rocket_side: RocketSide,
-    asteroid_shapes: Vec<AsteroidShape>,
+    xp_orbs: Vec<XpOrb>,
+    explosions: Vec<Explosion>,
+    radiation: Vec<Radiation>,
+    damage_numbers: Vec<DamageNumber>,
 
     black_holes: Vec<BlackHole>,
+    wormholes: Vec<Wormhole>,
+    white_holes: Vec<WhiteHole>,
+    gravity: ForceLaw,
 
     level_up: Option<LevelUp>,
     level: usize,
     xp: usize,
+    /// fractions of XP that were scaled by the mutators but not yet awarded
+    xp_remainder: f32,
     next_level_xp: usize,
     hostile_asteroids_per_second: f32,
     new_hostile_asteroids: f32,
     max_hostile_asteroid_speed: f32,
 
     available_upgrades: Vec<Rc<Upgrade>>,
+    inventory: Inventory,
+    show_stats: bool,
+    show_trajectories: bool,
+    missile_trajectories: bool,
     has_brakes: bool,
 
-    shields: f32,
     shield_regeneration_per_sec: f32,
-    rocket_stockpile: usize,
     rocket_production_progress: f32,
     rocket_production_per_sec: f32,
 
     bullet_reload_time: f32,
     rocket_reload_time: f32,
+    bullet_damage: f32,
+    rocket_damage: f32,
+
+    singularity_mass: f32,
+    singularity_duration: f32,
+    singularity_cooldown: f32,
+
+    radar_range: f32,
+    radar_detail: RadarDetail,
+
+    maneuvers: Maneuvers,
+    stats: RunStats,
 }
 
 impl MainState {
-    pub fn new() -> Self {
-        let ship = Ship {
-            pos: Vec2::new(screen_width() / 2., screen_height() / 2.),
-            rot: 0.,
-            vel: Vec2::new(0., 0.),
+    pub fn new(mode: GameMode, mutators: Vec<Mutator>) -> Self {
+        let ship = Ship::new(
+            VIEW_SIZE / 2.,
+            if mode.two_players() {
+                coop::PLAYER_ONE_CONTROLS
+            } else {
+                SOLO_CONTROLS
+            },
+        );
+        let partner = mode.two_players().then(|| {
+            let distance = match mode {
+                GameMode::Versus(_) => versus::START_DISTANCE,
+                _ => 60.,
+            };
+            Ship::new(
+                VIEW_SIZE / 2. + Vec2::new(-distance, 0.),
+                coop::PLAYER_TWO_CONTROLS,
+            )
+        });
+        // the daily challenge plays the same world for everyone
+        let challenge = (mode == GameMode::Daily).then(DailyChallenge::today);
+        let seed = match &challenge {
+            Some(challenge) => {
+                rand::srand(challenge.seed);
+                challenge.seed
+            }
+            None => random_seed(),
         };
-        let screen_center = Vec2::new(screen_width() / 2., screen_height() / 2.);
-
-        let asteroid_shapes: Vec<_> = (0..5).map(|_| AsteroidShape::new()).collect();
-
+        // the daily challenge comes with its own mutators
+        let mutators = match &challenge {
+            Some(challenge) => challenge.mutators.clone(),
+            None => mutators,
+        };
+        let difficulty = Difficulty::with_mutators(&mutators);
+        let mut world = World::new(seed);
         let mut asteroids = Vec::new();
-        for _ in 0..(ASTEROID_DENSITY * 5 * 5) {
-            let x = rand::gen_range(SHIP_HEIGHT * 10., 2.5 * screen_width());
-            let y = rand::gen_range(SHIP_HEIGHT * 10., 2.5 * screen_height());
-            let pos = Vec2::new(rand_signum() * x, rand_signum() * y);
-            asteroids.push(Asteroid::new(screen_center + pos, &asteroid_shapes));
-        }
+        let generated_asteroids = if mode.open_world() {
+            world.update(ship.pos, 1, &mut asteroids)
+        } else {
+            0
+        };
 
-        Self {
+        let mut state = Self {
+            mode,
+            lives: match mode {
+                GameMode::Classic => classic::CLASSIC_LIVES,
+                _ => 1,
+            },
+            wave: 0,
+            next_wave_at: None,
+            next_milestone: 0,
+            next_black_hole_id: 0,
+            last_net_id: 0,
+            mutators,
             game_t: 0.,
+            time_dilation: 1.,
             paused: false,
-            last_asteroid_generate_pos: ship.pos,
-            invulnerable_until: 0.,
-            colliding: false,
+            time_scale: 1.,
+            pending_steps: 0.,
+            sandbox_upgrade: 0,
+            invulnerable: false,
+            world,
+            biome: Biome::Field,
+            background: Biome::Field.background(),
             ship,
-            generated_asteroids: asteroids.len(),
+            partner,
+            view_scale: 1.,
+            generated_asteroids,
             bullets: Vec::new(),
             rockets: Vec::new(),
-            last_bullet_shot: 0.,
-            last_rocket_shot: 0.,
-            rocket_side: RocketSide::Right,
             asteroids,
-            asteroid_shapes,
+            xp_orbs: Vec::new(),
+            explosions: Vec::new(),
+            radiation: Vec::new(),
+            damage_numbers: Vec::new(),
 
             black_holes: Vec::new(),
+            wormholes: Vec::new(),
+            white_holes: Vec::new(),
+            gravity: ForceLaw::default(),
 
             level_up: None,
             level: 1,
             xp: 0,
             next_level_xp: 3,
-            hostile_asteroids_per_second: 4. / 60.,
+            xp_remainder: 0.,
+            hostile_asteroids_per_second: difficulty.hostile_asteroids_per_second,
             new_hostile_asteroids: 0.,
-            max_hostile_asteroid_speed: 1.,
+            max_hostile_asteroid_speed: difficulty.max_hostile_asteroid_speed,
 
             available_upgrades: make_upgrades(),
+            inventory: Inventory::default(),
+            show_stats: false,
+            show_trajectories: true,
+            missile_trajectories: false,
 
-            shields: 0.,
             shield_regeneration_per_sec: 0.,
 
-            rocket_stockpile: 2,
             rocket_production_progress: 0.,
-            rocket_production_per_sec: 0.,
+            rocket_production_per_sec: difficulty.rocket_production_per_sec,
             has_brakes: false,
 
             bullet_reload_time: 0.5,
             rocket_reload_time: 1.,
-        }
-    }
+            bullet_damage: difficulty.damage_factor,
+            rocket_damage: 3. * difficulty.damage_factor,
 
-    fn update(&mut self) -> Option<Box<dyn GameState>> {
-        if let Some(level_up) = &mut self.level_up {
-            if is_key_pressed(KeyCode::Enter) {
-                let upgrade = level_up.upgrade_choices[level_up.selected].clone();
-                self.level_up = None;
+            singularity_mass: 8.,
+            singularity_duration: 4.,
+            singularity_cooldown: 20.,
 
-                if !(upgrade.effect)(self) {
-                    self.available_upgrades.retain(|u| !Rc::ptr_eq(u, &upgrade))
-                }
-            } else {
-                if is_key_pressed(KeyCode::Down) {
-                    level_up.selected += 1;
-                } else if is_key_pressed(KeyCode::Up) {
-                    level_up.selected = level_up.upgrade_choices.len() + level_up.selected - 1;
-                }
-                level_up.selected = level_up.selected % level_up.upgrade_choices.len();
-                return None;
+            radar_range: 1500.,
+            radar_detail: RadarDetail::Basic,
+
+            maneuvers: Maneuvers::default(),
+            stats: RunStats::default(),
+            difficulty,
+        };
+        if !mode.open_world() {
+            state.spawn_wave();
+        }
+        if let Some(challenge) = challenge {
+            state.stats.day = challenge.day;
+        }
+        let difficulty = &state.difficulty;
+        state
+            .available_upgrades
+            .retain(|upgrade| difficulty.allows_upgrade(upgrade.label));
+        let starting_rockets = state.difficulty.starting_rockets;
+        for ship in state.ships_mut() {
+            ship.rocket_stockpile = starting_rockets;
+        }
+        state
+    }
+
+    fn apply_upgrade(&mut self, upgrade: Rc<Upgrade>) {
+        self.inventory.add(&upgrade);
+
+        if !(upgrade.effect)(self) {
+            self.available_upgrades.retain(|u| !Rc::ptr_eq(u, &upgrade))
+        }
+    }
+
+    fn black_hole_id(&mut self) -> u32 {
+        self.next_black_hole_id += 1;
+        self.next_black_hole_id - 1
+    }
+
+    /// How many black holes roam around the ship
+    fn black_hole_target(&self) -> usize {
+        (self.level + 5) * self.difficulty.black_hole_factor / 10
+    }
+
+    /// Adds XP, scaled by the mutators of the run
+    fn gain_xp(&mut self, xp: usize) {
+        self.xp += self.difficulty.scale_xp(xp, &mut self.xp_remainder);
+    }
+
+    pub fn run_stats(&self) -> RunStats {
+        RunStats {
+            mode: self.mode,
+            time: self.game_t,
+            level: self.level,
+            wave: self.wave,
+            kills: [
+                self.ship.kills,
+                self.partner.as_ref().map_or(0, |partner| partner.kills),
+            ],
+            mutators: self.mutators.clone(),
+            ..self.stats.clone()
+        }
+    }
+
+    /// Black holes that were not made by the player
+    fn natural_black_holes(&self) -> usize {
+        self.black_holes
+            .iter()
+            .filter(|bh| !bh.player_made())
+            .count()
+    }
+
+    /// Where the camera looks at: the ship in the open world, the whole screen in classic mode
+    fn view_center(&self) -> Vec2 {
+        if !self.mode.open_world() {
+            VIEW_SIZE / 2.
+        } else if let Some(partner) = &self.partner {
+            (self.ship.pos + partner.pos) / 2.
+        } else {
+            self.ship.pos
+        }
+    }
+
+    fn ships_mut(&mut self) -> impl Iterator<Item = &mut Ship> {
+        std::iter::once(&mut self.ship).chain(self.partner.as_mut())
+    }
+
+    /// The ships that are not wrecked
+    fn flying_ships(&self) -> impl Iterator<Item = &Ship> {
+        std::iter::once(&self.ship)
+            .chain(self.partner.as_ref())
+            .filter(|ship| ship.down.is_none())
+    }
+
+    /// Costs a life, the run is over when there are none left
+    fn ship_destroyed(
+        &mut self,
+        ship_idx: usize,
+        swallowed: bool,
+        game_t: f32,
+    ) -> Option<Box<dyn GameState>> {
+        if let GameMode::Versus(goal) = self.mode {
+            return self.versus_ship_destroyed(ship_idx, goal, game_t);
+        }
+        if self.partner.is_some() {
+            return self.wreck_ship(ship_idx, swallowed);
+        }
+
+        // practice never ends
+        if self.mode != GameMode::Sandbox {
+            self.lives = self.lives.saturating_sub(1);
+            if self.lives == 0 {
+                return Some(Box::new(MenuState::lost(self.run_stats())));
             }
         }
 
+        self.ship.pos = if self.mode == GameMode::Sandbox {
+            self.safe_spawn_pos(self.ship.pos)
+        } else {
+            self.view_center()
+        };
+        self.ship.vel = Vec2::ZERO;
+        self.ship.rot = 0.;
+        self.ship.invulnerable_until = game_t + RESPAWN_INVULNERABILITY;
+        None
+    }
+
+    /// How fast the proper time of something at `pos` passes compared to the world's time
+    fn time_dilation_at(&self, pos: Vec2) -> f32 {
+        self.black_holes
+            .iter()
+            .map(|bh| {
+                let dist = bh.pos().distance(pos);
+                (1. - TIME_DILATION_SCALE * bh.size / dist)
+                    .max(MIN_TIME_DILATION.powi(2))
+                    .sqrt()
+            })
+            .product::<f32>()
+            .max(MIN_TIME_DILATION)
+    }
+
+    /// Lets the picking player choose an upgrade, returns whether the world waits for the choice
+    fn update_level_up(&mut self) -> bool {
+        let Some(level_up) = &mut self.level_up else {
+            return false;
+        };
+        // only the player who picks navigates and confirms
+        let picker = match (&self.partner, level_up.picker) {
+            (Some(partner), 1) => partner,
+            _ => &self.ship,
+        };
+        let pressed = picker.input.pressed(picker.last_input);
+        if pressed.confirm {
+            let upgrade = level_up.upgrade_choices[level_up.selected].clone();
+            self.level_up = None;
+            self.apply_upgrade(upgrade);
+            return false;
+        }
+        if pressed.down {
+            level_up.selected += 1;
+        } else if pressed.up {
+            level_up.selected = level_up.upgrade_choices.len() + level_up.selected - 1;
+        }
+        level_up.selected %= level_up.upgrade_choices.len();
+        true
+    }
+
+    fn update(&mut self) -> Option<Box<dyn GameState>> {
+        for ship in self.ships_mut() {
+            let input = ship.controls.read();
+            ship.set_input(input);
+        }
+
+        if self.update_level_up() {
+            return None;
+        }
+
         if is_key_pressed(KeyCode::P) {
             self.paused = !self.paused
         }
 
-        if self.paused {
-            return None;
+        if is_key_pressed(KeyCode::Tab) {
+            self.show_stats = !self.show_stats
         }
 
-        let frame_t: f32 = get_frame_time();
-        self.game_t += frame_t;
-        let game_t = self.game_t;
+        if is_key_pressed(KeyCode::T) {
+            self.show_trajectories = !self.show_trajectories
+        }
 
-        let screen_size = Vec2::new(screen_width(), screen_height());
-        let screen_diag_length = screen_size.length();
-        let world_diag_length = screen_diag_length * 5.;
-        let rotation = self.ship.rot.to_radians();
-        // Forward
-        let acc = if is_key_down(KeyCode::Up) {
-            vec_from_rot(rotation) / 3.
-        } else if is_key_down(KeyCode::Down) && self.has_brakes {
-            -self.ship.vel / 20. // Break
-        } else {
-            -self.ship.vel / 1000. // Friction
-        };
+        if self.mode == GameMode::Sandbox {
+            if let Some(state) = self.update_sandbox() {
+                return Some(state);
+            }
+        }
 
-        // Shot
-        if is_key_down(KeyCode::Space) && game_t - self.last_bullet_shot > self.bullet_reload_time {
-            let rot_vec = vec_from_rot(rotation);
-            self.bullets.push(Bullet {
-                pos: self.ship.pos + rot_vec * SHIP_HEIGHT / 2.,
-                vel: rot_vec * 10.,
-                shot_at: game_t,
-                collided: false,
-            });
-            self.last_bullet_shot = game_t;
+        if self.paused || self.show_stats {
+            return None;
         }
 
-        // shoot rocket
-        if is_key_down(KeyCode::LeftAlt)
-            && game_t - self.last_rocket_shot > self.rocket_reload_time
-            && self.rocket_stockpile > 0
-        {
-            self.rocket_stockpile -= 1;
-            let sf = match self.rocket_side {
-                RocketSide::Left => -1.,
-                RocketSide::Right => 1.,
-            };
-            let rot_vec = vec_from_rot(rotation + sf * rand::gen_range(1.0, 1.4) * PI / 2.);
-            self.rocket_side = self.rocket_side.switch();
-            self.rockets.push(Rocket {
-                pos: self.ship.pos + rot_vec * SHIP_HEIGHT / 2.,
-                vel: self.ship.vel * 0.9 + rot_vec * rand::gen_range(0.7, 1.2),
-                rot: self.ship.rot,
-                shot_at: game_t,
-                collided: false,
-                steer: false,
-            });
-            self.last_rocket_shot = game_t;
+        // slow motion takes a fraction of a frame, fast forward takes several whole frames
+        let frame_time = get_frame_time();
+        if self.time_scale < 1. {
+            return self.step(frame_time, self.time_scale);
         }
+        self.pending_steps += self.time_scale;
+        while self.pending_steps >= 1. && self.level_up.is_none() {
+            self.pending_steps -= 1.;
+            if let Some(state) = self.step(frame_time, 1.) {
+                return Some(state);
+            }
+        }
+        None
+    }
+
+    /// Advances the world by `dt` frames, at most one, of a frame that took `frame_time` seconds
+    fn step(&mut self, frame_time: f32, dt: f32) -> Option<Box<dyn GameState>> {
+        let frame_t: f32 = frame_time * dt;
+        self.game_t += frame_t;
+        let game_t = self.game_t;
+
+        self.time_dilation = self.time_dilation_at(self.ship.pos);
+        let ship_frame_t = frame_t * self.time_dilation;
+        let partner_dilation = self
+            .partner
+            .as_ref()
+            .map_or(1., |partner| self.time_dilation_at(partner.pos));
+        let dilations = [self.time_dilation, partner_dilation];
+
+        let view_diag_length = VIEW_SIZE.length();
+        let world_diag_length = view_diag_length * 5.;
+
+        self.biome = self.world.biome_at(self.ship.pos);
+        let target = self.biome.background();
+        let fade = (frame_t * 2.).min(1.);
+        self.background = Color::new(
+            self.background.r + (target.r - self.background.r) * fade,
+            self.background.g + (target.g - self.background.g) * fade,
+            self.background.b + (target.b - self.background.b) * fade,
+            1.,
+        );
 
         // produce rockets
-        self.rocket_production_progress += self.rocket_production_per_sec * frame_t;
+        self.rocket_production_progress += self.rocket_production_per_sec * ship_frame_t;
         if self.rocket_production_progress >= 1. {
             let new_rockets = self.rocket_production_progress as usize;
             self.rocket_production_progress -= new_rockets as f32;
-            self.rocket_stockpile += new_rockets;
+            for ship in self.ships_mut() {
+                ship.rocket_stockpile += new_rockets;
+            }
         }
 
-        // regenerate shields
-        self.shields += self.shield_regeneration_per_sec * frame_t;
+        // advance the proper time of every ship and regenerate its shields with it
+        let regeneration = if self.difficulty.shields {
+            self.shield_regeneration_per_sec
+        } else {
+            0.
+        };
+        let ion_storm = self.biome == Biome::IonStorm;
+        for (ship, dilation) in self.ships_mut().zip(dilations) {
+            ship.t += frame_t * dilation;
+            ship.shields += regeneration * frame_t * dilation;
+            if ion_storm {
+                ship.shields = (ship.shields - ION_STORM_SHIELD_DRAIN * frame_t).max(0.);
+            }
+        }
 
-        // Steer
-        if is_key_down(KeyCode::Right) {
-            self.ship.rot += SHIP_ROTATION_SPEED;
-        } else if is_key_down(KeyCode::Left) {
-            self.ship.rot -= SHIP_ROTATION_SPEED;
+        // first half of the black holes' kick, the second half follows after everything moved
+        let gravity = &self.gravity;
+        let black_holes = &self.black_holes;
+        half_kick(black_holes, gravity, dt, &mut self.asteroids);
+        half_kick(black_holes, gravity, dt, &mut self.bullets);
+        half_kick(black_holes, gravity, dt, &mut self.rockets);
+        half_kick(black_holes, gravity, dt, &mut self.xp_orbs);
+        for ship in std::iter::once(&mut self.ship).chain(self.partner.as_mut()) {
+            if ship.down.is_none() {
+                half_kick(black_holes, gravity, dt, std::slice::from_mut(ship));
+            }
         }
 
-        // Euler integration
-        self.ship.vel += acc;
-        if self.ship.vel.length() > 5. {
-            self.ship.vel = self.ship.vel.normalize() * 5.;
+        let biome = self.biome;
+        let brakes = self.has_brakes && self.difficulty.brakes;
+        let mut singularities = Vec::new();
+        let ships = std::iter::once(&mut self.ship).chain(self.partner.as_mut());
+        for (ship_idx, ship) in ships.enumerate() {
+            if ship.down.is_some() {
+                continue;
+            }
+            let input = ship.input;
+            let rotation = ship.rot.to_radians();
+
+            // Shot
+            if input.shoot
+                && ship.t - ship.last_bullet_shot > self.bullet_reload_time
+                && self.difficulty.bullets
+            {
+                let rot_vec = vec_from_rot(rotation);
+                self.bullets.push(Bullet {
+                    pos: ship.pos + rot_vec * SHIP_HEIGHT / 2.,
+                    vel: rot_vec * 10.,
+                    shot_at: game_t,
+                    collided: false,
+                    owner: ship_idx,
+                    net_id: 0,
+                });
+                ship.last_bullet_shot = ship.t;
+            }
+
+            // shoot rocket
+            if input.rocket
+                && ship.t - ship.last_rocket_shot > self.rocket_reload_time
+                && ship.rocket_stockpile > 0
+            {
+                ship.rocket_stockpile -= 1;
+                let sf = match ship.rocket_side {
+                    RocketSide::Left => -1.,
+                    RocketSide::Right => 1.,
+                };
+                let rot_vec = vec_from_rot(rotation + sf * rand::gen_range(1.0, 1.4) * PI / 2.);
+                ship.rocket_side = ship.rocket_side.switch();
+                self.rockets.push(Rocket {
+                    pos: ship.pos + rot_vec * SHIP_HEIGHT / 2.,
+                    vel: ship.vel * 0.9 + rot_vec * rand::gen_range(0.7, 1.2),
+                    rot: ship.rot,
+                    shot_at: game_t,
+                    collided: false,
+                    steer: false,
+                    owner: ship_idx,
+                    net_id: 0,
+                });
+                ship.last_rocket_shot = ship.t;
+            }
+
+            // deploy singularity
+            if input.singularity && ship.t - ship.last_singularity > self.singularity_cooldown {
+                singularities.push(ship.pos + vec_from_rot(rotation) * SINGULARITY_RANGE);
+                ship.last_singularity = ship.t;
+            }
+
+            ship.fly(biome, brakes, dt);
         }
-        self.ship.pos += self.ship.vel;
-        //self.ship.pos = wrap_around(&self.ship.pos);
+        for pos in singularities {
+            let id = self.black_hole_id();
+            let mut bh = BlackHole::new(id, pos, Vec2::ZERO, self.singularity_mass);
+            bh.expires_at = Some(game_t + self.singularity_duration);
+            self.black_holes.push(bh);
+        }
+        self.update_coop(game_t, frame_t, dt);
 
         // Move each bullet
         for bullet in self.bullets.iter_mut() {
-            bullet.pos += bullet.vel;
-            //bullet.pos = wrap_around(&bullet.pos);
+            bullet.pos += bullet.vel * dt;
         }
 
         // Move each rocket
@@ -535,144 +1525,273 @@ impl MainState {
 
                     if let Some(target) = target {
                         let angle = rrot.angle_between(target.pos - rocket.pos).to_degrees();
-                        rocket.rot += angle.min(10.);
+                        rocket.rot += angle.min(10.) * dt;
                     }
                 }
 
                 // accelerate rocket
                 let acc = 0.6 * vec_from_rot(rocket.rot.to_radians());
-                rocket.vel += acc;
+                rocket.vel += acc * dt;
                 if rocket.vel.length() > 15. {
                     rocket.vel = rocket.vel.normalize() * 15.;
                 }
             }
-            rocket.pos += rocket.vel;
-            //rocket.pos = wrap_around(&rocket.pos);
+            rocket.pos += rocket.vel * dt;
         }
 
+        let ship_positions: Vec<_> = self.flying_ships().map(|ship| ship.pos).collect();
+        let lead_pos = self.ship.pos;
+        let nearest_ship = |pos: Vec2| {
+            ship_positions
+                .iter()
+                .copied()
+                .min_by(|a, b| a.distance(pos).total_cmp(&b.distance(pos)))
+                .unwrap_or(lead_pos)
+        };
+
         // Move each asteroid
         for asteroid in self.asteroids.iter_mut() {
-            asteroid.pos += asteroid.vel;
-            //asteroid.pos = wrap_around(&asteroid.pos);
-            asteroid.rot += asteroid.rot_speed;
+            if asteroid.material == AsteroidMaterial::Magnetic {
+                let to_ship = nearest_ship(asteroid.pos) - asteroid.pos;
+                if to_ship.length() < MAGNETIC_RANGE {
+                    asteroid.vel += to_ship.normalize_or_zero() * 0.02 * dt;
+                }
+            }
+            asteroid.pos += asteroid.vel * dt;
+            asteroid.rot += asteroid.rot_speed * dt;
         }
 
+        // Move each xp orb
+        let mut orb_xp = 0;
+        for orb in self.xp_orbs.iter_mut() {
+            let to_ship = nearest_ship(orb.pos) - orb.pos;
+            if to_ship.length() < SHIP_HEIGHT {
+                orb.collided = true;
+                orb_xp += orb.xp;
+            } else if to_ship.length() < XP_ORB_PICKUP_RANGE {
+                orb.vel += to_ship.normalize() * 0.3 * dt;
+            }
+            orb.vel *= 0.98_f32.powf(dt);
+            orb.pos += orb.vel * dt;
+        }
+        self.gain_xp(orb_xp);
+
+        // Move each radiation particle
+        for particle in self.radiation.iter_mut() {
+            particle.pos += particle.vel * dt;
+        }
+
+        if !self.mode.open_world() {
+            self.wrap_entities();
+        }
+
+        collide_asteroids(&mut self.asteroids);
+
         // Bullet lifetime
         self.bullets.retain(|bullet| bullet.shot_at + 2.5 > game_t);
 
         let mut new_asteroids = Vec::new();
-        let mut colliding = false;
-        for asteroid in self.asteroids.iter_mut() {
+        let mut colliding = [false; 2];
+        let mut hits = Vec::new();
+        let mut destroyed_ships = Vec::new();
+        for (asteroid_idx, asteroid) in self.asteroids.iter().enumerate() {
             // Asteroid/ship collision
-            if (asteroid.pos - self.ship.pos).length() < asteroid.size + SHIP_HEIGHT / 3. {
-                if !colliding && !self.colliding {
-                    if self.shields > 1. {
-                        self.shields -= 1.;
-                        self.invulnerable_until = game_t + 0.3;
+            let ships = std::iter::once(&mut self.ship).chain(self.partner.as_mut());
+            for (ship_idx, ship) in ships.enumerate() {
+                if ship.down.is_some() || !asteroid.touches(ship.pos, SHIP_HEIGHT / 3.) {
+                    continue;
+                }
+                if !colliding[ship_idx] && !ship.colliding {
+                    let hit_cost = self.difficulty.shield_hit_cost;
+                    if ship.shields > hit_cost {
+                        ship.shields -= hit_cost;
+                        ship.invulnerable_until = game_t + 0.3;
                     }
 
-                    if game_t < self.invulnerable_until {
-                        let collision_vec = asteroid.pos - self.ship.pos;
-                        self.ship.vel -= 6. * self.ship.vel.project_onto(collision_vec);
+                    if game_t < ship.invulnerable_until {
+                        let collision_vec = asteroid.pos - ship.pos;
+                        ship.vel -= 6. * ship.vel.project_onto(collision_vec);
                     } else {
-                        return Some(Box::new(MenuState::Lost));
+                        destroyed_ships.push(ship_idx);
                     }
                 }
-                colliding = true;
+                colliding[ship_idx] = true;
             }
 
-            let mut hit_vel = None;
+            let mut hit = None;
 
             // Asteroid/bullet collision
             for bullet in self.bullets.iter_mut() {
-                if (asteroid.pos - bullet.pos).length() < asteroid.size {
+                if asteroid.touches(bullet.pos, bullet.radius()) {
                     bullet.collided = true;
-                    hit_vel = Some(bullet.vel);
+                    hit = Some((bullet.pos, bullet.vel, self.bullet_damage));
                     break;
                 }
             }
 
             // Asteroid/rocket collision
             for rocket in self.rockets.iter_mut() {
-                if (asteroid.pos - rocket.pos).length() < (asteroid.size + ROCKET_SIZE) {
+                if asteroid.touches(rocket.pos, ROCKET_SIZE) {
                     rocket.collided = true;
-                    hit_vel = Some(rocket.vel);
+                    hit = Some((rocket.pos, rocket.vel, self.rocket_damage));
                     break;
                 }
             }
 
-            if let Some(hit_vel) = hit_vel {
-                asteroid.collided = true;
-                self.xp += 1;
-
-                // Break the asteroid
-                if asteroid.sides > 3 {
-                    new_asteroids.push(Asteroid {
-                        pos: asteroid.pos,
-                        vel: Vec2::new(hit_vel.y, -hit_vel.x).normalize() * rand::gen_range(1., 3.),
-                        rot: rand::gen_range(0., 360.),
-                        rot_speed: rand::gen_range(-2., 2.),
-                        size: asteroid.size * 0.8,
-                        sides: asteroid.sides - 1,
-                        collided: false,
-                        shape_idx: rand::gen_range(0, self.asteroid_shapes.len()),
-                    });
-                    new_asteroids.push(Asteroid {
-                        pos: asteroid.pos,
-                        vel: Vec2::new(-hit_vel.y, hit_vel.x).normalize() * rand::gen_range(1., 3.),
-                        rot: rand::gen_range(0., 360.),
-                        rot_speed: rand::gen_range(-2., 2.),
-                        size: asteroid.size * 0.8,
-                        sides: asteroid.sides - 1,
-                        collided: false,
-                        shape_idx: rand::gen_range(0, self.asteroid_shapes.len()),
-                    })
-                }
+            if let Some((hit_pos, hit_vel, damage)) = hit {
+                hits.push((asteroid_idx, hit_pos, hit_vel, damage));
                 break;
             }
         }
 
-        self.colliding = colliding;
+        // Radiation damages and pushes asteroids. Ships only lose shields, the particles are too
+        // light to push them off course.
+        for particle in self.radiation.iter_mut() {
+            let hit_ship = std::iter::once(&mut self.ship)
+                .chain(self.partner.as_mut())
+                .find(|ship| {
+                    ship.down.is_none() && particle.pos.distance(ship.pos) < SHIP_HEIGHT / 3.
+                });
+            if let Some(ship) = hit_ship {
+                particle.collided = true;
+                let drain = particle.damage / 4. * self.difficulty.shield_hit_cost;
+                ship.shields = (ship.shields - drain).max(0.);
+                continue;
+            }
+            let hit = self
+                .asteroids
+                .iter()
+                .position(|asteroid| !asteroid.collided && asteroid.touches(particle.pos, 0.));
+            if let Some(asteroid_idx) = hit {
+                particle.collided = true;
+                hits.push((asteroid_idx, particle.pos, particle.vel, particle.damage));
+            }
+        }
 
-        let mut new_black_holes = Vec::new();
+        // Resolve hits, volatile asteroids may hit their neighbours in turn
+        while let Some((asteroid_idx, hit_pos, hit_vel, damage)) = hits.pop() {
+            let asteroid = &mut self.asteroids[asteroid_idx];
+            if asteroid.collided {
+                continue;
+            }
 
-        for (i, bh1) in self.black_holes.iter().enumerate() {
-            for (j, bh2) in self.black_holes.iter().enumerate() {
-                if i > j {
-                    let dist = bh1.pos().distance(bh2.pos());
-                    let dist_vec = bh2.pos() - bh1.pos();
-                    let comb_size = bh1.size + bh2.size;
-                    bh1.vel
-                        .set(bh1.vel() + dist_vec.normalize() * (70. * comb_size / dist.powi(2)));
-                    bh2.vel
-                        .set(bh2.vel() - dist_vec.normalize() * (70. * comb_size / dist.powi(2)));
-
-                    if bh1.pos().distance(bh2.pos()) < comb_size {
-                        bh1.collided.set(true);
-                        bh2.collided.set(true);
-                        new_black_holes.push(BlackHole {
-                            pos: Cell::new(bh1.pos() + (bh2.size / comb_size) * dist_vec),
-                            vel: Cell::new(
-                                (bh1.size / comb_size) * bh1.vel()
-                                    + (bh2.size / comb_size) * bh2.vel(),
-                            ),
-                            collided: Cell::new(false),
-                            size: comb_size.min(400.), // this is so not how physics works
-                        })
+            self.damage_numbers.push(DamageNumber {
+                pos: hit_pos,
+                amount: damage,
+                at: game_t,
+            });
+            asteroid.vel += hit_vel * damage * HIT_IMPULSE_PER_DAMAGE / asteroid.mass();
+
+            asteroid.hp -= damage;
+            if asteroid.hp > 0. {
+                continue;
+            }
+
+            asteroid.collided = true;
+            self.gain_xp(1);
+            self.stats.asteroids_destroyed += 1;
+
+            // Break the asteroid
+            let asteroid = &self.asteroids[asteroid_idx];
+            new_asteroids.extend(asteroid.split(hit_vel));
+
+            match asteroid.material {
+                AsteroidMaterial::Volatile => {
+                    let pos = asteroid.pos;
+                    let radius = asteroid.size * VOLATILE_EXPLOSION_FACTOR;
+                    self.explosions.push(Explosion {
+                        pos,
+                        radius,
+                        at: game_t,
+                    });
+
+                    for (other_idx, other) in self.asteroids.iter().enumerate() {
+                        if !other.collided && other.pos.distance(pos) < radius + other.size {
+                            let dir = (other.pos - pos).normalize_or_zero();
+                            let hit_pos = other.pos - dir * other.size;
+                            hits.push((other_idx, hit_pos, dir * 10., EXPLOSION_DAMAGE));
+                        }
                     }
                 }
+                AsteroidMaterial::Crystal => self.xp_orbs.push(XpOrb {
+                    pos: asteroid.pos,
+                    vel: asteroid.vel,
+                    xp: CRYSTAL_XP,
+                    collided: false,
+                }),
+                _ => {}
             }
         }
 
-        for bh in self.black_holes.iter() {
-            bh.pos.set(bh.pos() + bh.vel());
+        let ships = std::iter::once(&mut self.ship).chain(self.partner.as_mut());
+        for (ship, colliding) in ships.zip(colliding) {
+            ship.colliding = colliding;
+        }
+        for ship_idx in destroyed_ships {
+            if let Some(state) = self.ship_destroyed(ship_idx, false, game_t) {
+                return Some(state);
+            }
+        }
+        if let Some(state) = self.update_versus(game_t) {
+            return Some(state);
+        }
+
+        // Black holes attract each other and merge when their horizons touch
+        let mut bodies: Vec<_> = self.black_holes.iter().map(|bh| bh.body).collect();
+        self.gravity.step(&mut bodies, dt);
+        for (bh, body) in self.black_holes.iter_mut().zip(bodies) {
+            bh.body = body;
+        }
+
+        let mut merges = Vec::new();
+        for i in 0..self.black_holes.len() {
+            let (left, right) = self.black_holes.split_at_mut(i + 1);
+            let bh1 = &mut left[i];
+            for bh2 in right.iter_mut() {
+                if bh1.collided || bh2.collided {
+                    continue;
+                }
+                if bh1.pos().distance(bh2.pos()) < bh1.size + bh2.size {
+                    bh1.collided = true;
+                    bh2.collided = true;
+                    // feeding a natural black hole makes it permanent
+                    let expires_at = bh1
+                        .expires_at
+                        .zip(bh2.expires_at)
+                        .map(|(t1, t2)| t1.max(t2));
+                    merges.push((
+                        gravity::merge(&bh1.body, &bh2.body),
+                        expires_at,
+                        bh1.event_horizon || bh2.event_horizon,
+                    ));
+                }
+            }
+        }
+        let mut new_black_holes = Vec::new();
+        for (body, expires_at, event_horizon) in merges {
+            let mut merged = BlackHole::from_body(self.black_hole_id(), body);
+            merged.expires_at = expires_at;
+            merged.event_horizon = event_horizon;
+            new_black_holes.push(merged);
+        }
 
-            fn affect_obj(bh: &BlackHole, obj: &mut impl BlackHoleEffected) -> bool {
+        let mut swallowed = Vec::new();
+        let mut swallowed_ships = Vec::new();
+        let mut eaten_xp = 0;
+        for bh in self.black_holes.iter() {
+            fn affect_obj(
+                bh: &BlackHole,
+                gravity: &ForceLaw,
+                dt: f32,
+                obj: &mut impl BlackHoleEffected,
+            ) -> bool {
                 let pos = obj.pos();
                 let dist = bh.pos().distance(pos);
-                *obj.vel() += (bh.pos() - pos).normalize() * (70. * bh.size / dist.powi(2));
+                *obj.vel() += bh.pull_at(gravity, pos) * dt / 2.;
 
-                let collided = dist < bh.size + obj.radius();
+                // black holes that merged this frame still pull, the merged one eats from the next
+                // frame on
+                let collided = !bh.collided && dist < bh.size + obj.radius();
 
                 if collided {
                     obj.collide();
@@ -681,71 +1800,121 @@ impl MainState {
                 collided
             }
 
-            fn affect_objs(bh: &BlackHole, objs: &mut Vec<impl BlackHoleEffected>) {
+            /// Returns the swallowed mass and the number of swallowed objects
+            fn affect_objs(
+                bh: &BlackHole,
+                gravity: &ForceLaw,
+                dt: f32,
+                objs: &mut [impl BlackHoleEffected],
+            ) -> (f32, usize) {
+                let mut mass = 0.;
+                let mut count = 0;
                 for obj in objs.iter_mut() {
-                    affect_obj(bh, obj);
+                    if affect_obj(bh, gravity, dt, obj) {
+                        mass += obj.mass();
+                        count += 1;
+                    }
                 }
+                (mass, count)
             }
 
-            affect_objs(bh, &mut self.bullets);
-            affect_objs(bh, &mut self.rockets);
-            affect_objs(bh, &mut self.asteroids);
-            if affect_obj(bh, &mut self.ship) {
-                return Some(Box::new(MenuState::Lost));
+            let gravity = &self.gravity;
+            let (asteroid_mass, asteroids_eaten) =
+                affect_objs(bh, gravity, dt, &mut self.asteroids);
+            swallowed.push(
+                affect_objs(bh, gravity, dt, &mut self.bullets).0
+                    + affect_objs(bh, gravity, dt, &mut self.rockets).0
+                    + asteroid_mass
+                    + affect_objs(bh, gravity, dt, &mut self.xp_orbs).0,
+            );
+            if bh.player_made() {
+                eaten_xp += asteroids_eaten;
+                self.stats.asteroids_destroyed += asteroids_eaten;
+            }
+            // invulnerable sandbox ships are pulled, but not swallowed
+            let invulnerable = self.invulnerable;
+            let ships = std::iter::once(&mut self.ship).chain(self.partner.as_mut());
+            for (ship_idx, ship) in ships.enumerate() {
+                if ship.down.is_none() && affect_obj(bh, gravity, dt, ship) && !invulnerable {
+                    swallowed_ships.push(ship_idx);
+                }
             }
         }
-
-        // generate new asteroids
-        if self.last_asteroid_generate_pos.distance(self.ship.pos) > 50. {
-            let gen_vec = self.ship.pos - self.last_asteroid_generate_pos;
-            let asteroid_per_pixel = ASTEROID_DENSITY as f32 / (screen_height() * screen_width());
-            let new_x_pixel = gen_vec.x.abs() * screen_height();
-            let new_y_pixel = gen_vec.y.abs() * screen_width();
-            let new_pixels = 5. * new_x_pixel + 5. * new_y_pixel - gen_vec.x * gen_vec.y;
-            let amount_new_asteroids = asteroid_per_pixel * new_pixels;
-            info!("new asteroids: {}", amount_new_asteroids);
-            let amount_new_asteroids = rand::gen_range(
-                (0.8 * amount_new_asteroids) as usize,
-                2 + (amount_new_asteroids * 1.2) as usize,
-            );
-
-            for _ in 0..amount_new_asteroids {
-                let x_pixel_ratio = new_x_pixel / (new_x_pixel + new_y_pixel);
-                let pos = if rand::gen_range(0., 1.) < x_pixel_ratio {
-                    // x
-                    Vec2::new(
-                        gen_vec.x.signum()
-                            * (2.5 * screen_width() - rand::gen_range(0., gen_vec.x.abs())),
-                        rand::gen_range(-2.5 * screen_height(), 2.5 * screen_height()),
-                    )
-                } else {
-                    // y
-                    Vec2::new(
-                        rand::gen_range(-2.5 * screen_width(), 2.5 * screen_width()),
-                        gen_vec.y.signum()
-                            * (2.5 * screen_height() - rand::gen_range(0., gen_vec.y.abs())),
-                    )
-                };
-
-                self.generated_asteroids += 1;
-                new_asteroids.push(Asteroid::new(self.ship.pos + pos, &self.asteroid_shapes))
+        self.gain_xp(eaten_xp);
+        self.apply_anomalies(dt);
+        for ship_idx in swallowed_ships {
+            if let Some(state) = self.ship_destroyed(ship_idx, true, game_t) {
+                return Some(state);
             }
+        }
 
-            self.last_asteroid_generate_pos = self.ship.pos;
+        // Black holes grow by feeding, evaporate by Hawking radiation and collapse when too big
+        for (bh, swallowed) in self.black_holes.iter_mut().zip(swallowed) {
+            if bh.event_horizon {
+                continue;
+            }
+            let mass = bh.mass() + swallowed * BLACK_HOLE_FEED;
+            let loss = HAWKING_RATE / mass.powi(2) * frame_t;
+            bh.set_mass(mass - loss);
+            bh.radiation += loss * RADIATION_PER_MASS;
+            let particles = bh.radiation as usize;
+            bh.radiation -= particles as f32;
+            let burst = Radiation::burst(bh.pos(), bh.size, particles, RADIATION_DAMAGE, game_t);
+            self.radiation.extend(burst.into_iter().map(|mut r| {
+                r.vel += bh.vel();
+                r
+            }));
+
+            if bh.size < BLACK_HOLE_MIN_SIZE || bh.expires_at.is_some_and(|t| t <= game_t) {
+                bh.collided = true;
+                self.radiation.extend(Radiation::burst(
+                    bh.pos(),
+                    bh.size,
+                    20,
+                    RADIATION_DAMAGE,
+                    game_t,
+                ));
+            } else if bh.size > BLACK_HOLE_COLLAPSE_SIZE {
+                bh.collided = true;
+                self.explosions.push(Explosion {
+                    pos: bh.pos(),
+                    radius: bh.size * 2.,
+                    at: game_t,
+                });
+                self.radiation.extend(Radiation::burst(
+                    bh.pos(),
+                    bh.size,
+                    COLLAPSE_RADIATION,
+                    COLLAPSE_RADIATION_DAMAGE,
+                    game_t,
+                ));
+            }
         }
 
-        // generate hostile asteroids
-        self.new_hostile_asteroids += self.hostile_asteroids_per_second * frame_t;
+        if self.mode.open_world() {
+            self.update_anomalies(view_diag_length, world_diag_length, dt);
+
+            // generate hostile asteroids
+            self.new_hostile_asteroids += self.hostile_asteroids_per_second * frame_t;
+        }
 
         while self.new_hostile_asteroids >= 1. {
             self.new_hostile_asteroids -= 1.;
 
-            let pos = self.ship.pos
+            // with two ships, either of them is targeted
+            let target = match &self.partner {
+                Some(partner) if rand::gen_range(0, 2) == 1 => partner.pos,
+                _ => self.ship.pos,
+            };
+            let pos = target
                 + Vec2::from_angle(rand::gen_range(0.0_f32, 360.).to_radians())
-                    * rand::gen_range(screen_diag_length, screen_diag_length * 2.);
-            let mut asteroid = Asteroid::new(pos, &self.asteroid_shapes);
-            asteroid.vel = (self.ship.pos - pos).normalize()
-                * rand::gen_range(1., self.max_hostile_asteroid_speed);
+                    * rand::gen_range(view_diag_length, view_diag_length * 2.);
+            let biome = self.world.biome_at(pos);
+            let material = AsteroidMaterial::random(self.level, biome, rand::gen_range(0., 1.));
+            let mut asteroid = Asteroid::new(pos, material);
+            asteroid.hostile = true;
+            asteroid.vel =
+                (target - pos).normalize() * rand::gen_range(1., self.max_hostile_asteroid_speed);
             new_asteroids.push(asteroid);
         }
 
@@ -755,34 +1924,73 @@ impl MainState {
         self.rockets
             .retain(|rocket| rocket.shot_at + ROCKET_LIFETIME > game_t && !rocket.collided);
         self.asteroids.retain(|asteroid| {
-            !asteroid.collided && self.ship.pos.distance(asteroid.pos) < world_diag_length / 2.
+            if asteroid.collided {
+                self.world.destroyed(asteroid);
+                return false;
+            }
+            asteroid.origin.is_some()
+                || self.ship.pos.distance(asteroid.pos) < world_diag_length / 2.
         });
         self.asteroids.append(&mut new_asteroids);
+        if self.mode.open_world() {
+            let center = self.view_center();
+            self.generated_asteroids += self.world.update(center, self.level, &mut self.asteroids);
+        }
+        self.xp_orbs.retain(|orb| {
+            !orb.collided && self.ship.pos.distance(orb.pos) < world_diag_length / 2.
+        });
+        self.explosions
+            .retain(|explosion| explosion.at + EXPLOSION_DURATION > game_t);
+        self.damage_numbers
+            .retain(|number| number.at + DAMAGE_NUMBER_DURATION > game_t);
+        self.radiation
+            .retain(|particle| !particle.collided && particle.at + RADIATION_LIFETIME > game_t);
         self.black_holes.retain(|bh| {
-            !bh.collided.get() && self.ship.pos.distance(bh.pos()) < world_diag_length / 2.
+            !bh.collided
+                && (bh.event_horizon || self.ship.pos.distance(bh.pos()) < world_diag_length / 2.)
         });
         self.black_holes.append(&mut new_black_holes);
 
-        while self.black_holes.len() < (self.level + 5) / 10 {
+        while self.mode.open_world() && self.natural_black_holes() < self.black_hole_target() {
             // self.level / 10 {
             let pos = self.ship.pos
                 + Vec2::from_angle(rand::gen_range(0.0_f32, 360.).to_radians())
-                    * rand::gen_range(screen_diag_length * 0.4, screen_diag_length * 2.);
+                    * rand::gen_range(view_diag_length * 0.4, view_diag_length * 2.);
             let rand_vec = Vec2::new(
-                rand::gen_range(-0.5, 0.5) * screen_width(),
-                rand::gen_range(-0.5, 0.5) * screen_height(),
+                rand::gen_range(-0.5, 0.5) * VIEW_SIZE.x,
+                rand::gen_range(-0.5, 0.5) * VIEW_SIZE.y,
+            );
+            let bh = BlackHole::new(
+                self.black_hole_id(),
+                pos,
+                ((self.ship.pos + rand_vec) - pos).normalize() * rand::gen_range(1., 3.),
+                rand::gen_range(5., 20.),
             );
-            let bh = BlackHole {
-                pos: Cell::new(pos),
-                vel: Cell::new(
-                    ((self.ship.pos + rand_vec) - pos).normalize() * rand::gen_range(1., 3.),
-                ),
-                size: rand::gen_range(5., 20.),
-                collided: Cell::new(false),
-            };
             self.black_holes.push(bh);
         }
 
+        // gravity wells keep a cluster of black holes around their center
+        while self.mode.open_world()
+            && self.natural_black_holes()
+                < self.black_hole_target() + self.biome.extra_black_holes()
+        {
+            let pos = self.world.sector_center(self.ship.pos)
+                + Vec2::from_angle(rand::gen_range(0.0_f32, 360.).to_radians())
+                    * rand::gen_range(0., view_diag_length);
+            // the sector center can be far away, stay inside the area where black holes are kept
+            let pos =
+                self.ship.pos + (pos - self.ship.pos).clamp_length_max(world_diag_length * 0.45);
+            let bh = BlackHole::new(
+                self.black_hole_id(),
+                pos,
+                Vec2::from_angle(rand::gen_range(0., 2. * PI)) * 0.3,
+                rand::gen_range(10., 30.),
+            );
+            self.black_holes.push(bh);
+        }
+
+        self.track_maneuvers();
+
         // update level
         while self.xp >= self.next_level_xp {
             self.level += 1;
@@ -790,42 +1998,315 @@ impl MainState {
             self.next_level_xp =
                 ((self.next_level_xp as f32 * 1.1) as usize).max(self.next_level_xp + 1);
 
-            self.hostile_asteroids_per_second *= 1.2;
-            self.max_hostile_asteroid_speed *= 1.08;
+            self.hostile_asteroids_per_second *= self.difficulty.hostile_asteroid_rate_growth;
+            self.max_hostile_asteroid_speed *= self.difficulty.hostile_asteroid_speed_growth;
 
-            self.level_up = Some(LevelUp::new(3, self.available_upgrades.clone()))
+            let mut level_up = LevelUp::new(3, self.available_upgrades.clone());
+            // co-op players pick their upgrades in turn
+            if self.partner.is_some() {
+                level_up.picker = self.level % 2;
+            }
+            self.level_up = Some(level_up);
         }
 
         // You win?
-        /*if self.asteroids.len() == 0 {
-            return Some(Box::new(MenuState::Won));
-        }*/
+        if self.mode == GameMode::Classic {
+            return self.update_waves(game_t);
+        }
+        if let Some(state) = self.update_timed(game_t, frame_t) {
+            return Some(state);
+        }
 
         None
     }
 
+    /// Zoom so that the whole view fits into the window, whatever its size
+    fn zoom(&self) -> f32 {
+        (screen_width() / VIEW_SIZE.x).min(screen_height() / VIEW_SIZE.y) * self.view_scale
+    }
+
     fn render(&self) {
-        let screen_size = Vec2::new(screen_width(), screen_height());
-        let screen_diag_length = screen_size.length();
-        let rotation = self.ship.rot.to_radians();
+        let zoom = self.zoom();
+
+        clear_background(self.background);
+
+        match (&self.partner, self.mode) {
+            (Some(partner), GameMode::Versus(_)) => {
+                // player two sits on the left side of the keyboard and gets the left half
+                let half = screen_width() as i32 / 2;
+                let height = screen_height() as i32;
+                self.render_view(partner.pos, partner, zoom, Some((0, 0, half, height)));
+                let viewport = Some((half, 0, half, height));
+                self.render_view(self.ship.pos, &self.ship, zoom, viewport);
+            }
+            _ => self.render_view(self.view_center(), &self.ship, zoom, None),
+        }
+
+        set_default_camera();
+
+        draw_text(
+            &format!(
+                "Fps: {}, Asteroids: {} ({}), Bullets: {}, Rockets: {}",
+                get_fps(),
+                self.asteroids.len(),
+                self.generated_asteroids,
+                self.bullets.len(),
+                self.rockets.len()
+            ),
+            30.,
+            screen_height() - 30.,
+            30.,
+            BLACK,
+        );
 
-        fn make_camera(pos: Vec2) -> Camera2D {
-            let cam_pos = pos - Vec2::new(screen_width(), -screen_height()) / 2.;
-            let rect = Rect::new(cam_pos.x, cam_pos.y, screen_width(), -screen_height());
-            Camera2D::from_display_rect(rect)
+        draw_text(
+            &format!(
+                "Level {}, XP no next Level: {}",
+                self.level,
+                self.next_level_xp - self.xp
+            ),
+            30.,
+            30.,
+            30.,
+            BLACK,
+        );
+
+        let singularity_in = self.ship.last_singularity + self.singularity_cooldown - self.ship.t;
+        let per_ship = |value: fn(&Ship) -> usize| {
+            let values: Vec<_> = std::iter::once(&self.ship)
+                .chain(self.partner.as_ref())
+                .map(|ship| value(ship).to_string())
+                .collect();
+            values.join(" / ")
+        };
+        if let GameMode::Versus(goal) = self.mode {
+            self.render_versus_hud(goal);
+        } else {
+            draw_text(
+                &format!(
+                    "Missiles: {}  Shields: {}  Singularity: {}",
+                    per_ship(|ship| ship.rocket_stockpile),
+                    per_ship(|ship| ship.shields as usize),
+                    if singularity_in > 0. {
+                        format!("{:.0}s", singularity_in.ceil())
+                    } else {
+                        "ready".to_string()
+                    }
+                ),
+                30.,
+                60.,
+                30.,
+                BLACK,
+            );
+        }
+
+        if self.mode.open_world() {
+            draw_text(self.biome.name(), 30., 90., 30., BLACK);
+        } else {
+            self.render_waves();
         }
 
-        clear_background(LIGHTGRAY);
+        if self.mode == GameMode::Sandbox {
+            self.render_sandbox();
+        }
+
+        self.render_clock();
+        self.render_countdown();
+        self.render_callouts();
+
+        // both track player one's ship, the indicators sit on the edge of the shared view
+        if self.mode.open_world() && !matches!(self.mode, GameMode::Versus(_)) {
+            self.render_radar();
+            self.render_threat_indicators(zoom);
+        }
+
+        // inventory
+        for (idx, (upgrade, count)) in self.inventory.items.iter().enumerate() {
+            let x = screen_width() - 30. - 70. * (idx + 1) as f32;
+            draw_rectangle(x, 20., 60., 60., BLACK);
+            draw_rectangle(x + 3., 23., 54., 54., GRAY);
+            draw_centered_text(upgrade.label, x + 30., 42., 26., BLACK);
+            draw_centered_text(&format!("x{}", count), x + 30., 64., 22., BLACK);
+        }
+
+        if let Some(level_up) = &self.level_up {
+            let uc = level_up.upgrade_choices.len();
+
+            let th = 60.;
+            let h = 20. + th + (80 * uc) as f32;
+            let w = 600.;
+
+            let x = screen_width() / 2. - w / 2.;
+            let y = screen_height() / 2. - h / 2.;
+
+            draw_rectangle(x, y, w, h, GRAY);
+
+            if let Some(partner) = &self.partner {
+                let confirm = match level_up.picker {
+                    1 => partner.controls.confirm,
+                    _ => self.ship.controls.confirm,
+                };
+                let title = format!(
+                    "Level Up! Player {} picks ({:?})",
+                    level_up.picker + 1,
+                    confirm
+                );
+                draw_centered_text(&title, screen_width() / 2., y + 20., 36., BLACK);
+            } else {
+                draw_centered_text("Level Up!", screen_width() / 2., y + 20., 60., BLACK);
+            }
+
+            for (idx, upgrade) in level_up.upgrade_choices.iter().enumerate() {
+                let is_selected = idx == level_up.selected;
+                let idx = idx as f32;
+                draw_rectangle(x + 20., y + idx * 80. + th + 20., w - 40., 60., BLACK);
+                let color = if is_selected { LIGHTGRAY } else { GRAY };
+                draw_rectangle(x + 25., y + idx * 80. + th + 25., w - 50., 50., color);
+
+                draw_centered_text(
+                    &(upgrade.desc)(self),
+                    screen_width() / 2.,
+                    y + idx * 80. + th + 45.,
+                    50.,
+                    BLACK,
+                )
+            }
+        } else if self.show_stats {
+            let stats = [
+                format!("Level: {}", self.level),
+                format!("Time dilation: x{:.2}", self.time_dilation),
+                format!(
+                    "Bullet reload: {:.2}s ({:.1} shots/s)",
+                    self.bullet_reload_time,
+                    1. / self.bullet_reload_time
+                ),
+                format!("Bullet damage: {:.2}", self.bullet_damage),
+                format!("Missile reload: {:.2}s", self.rocket_reload_time),
+                format!("Missile damage: {:.2}", self.rocket_damage),
+                format!("Missiles: {}", self.ship.rocket_stockpile),
+                format!(
+                    "Missile production: {:.1}/s",
+                    self.rocket_production_per_sec
+                ),
+                format!(
+                    "Shields: {:.2} (+{:.1}/min)",
+                    self.ship.shields,
+                    self.shield_regeneration_per_sec * 60.
+                ),
+                format!(
+                    "Singularity [Shift]: mass {:.1}, {:.1}s, cooldown {:.1}s",
+                    self.singularity_mass, self.singularity_duration, self.singularity_cooldown
+                ),
+                format!("Brakes: {}", if self.has_brakes { "yes" } else { "no" }),
+                format!(
+                    "Mutators: {} (XP x{:.2})",
+                    if self.mutators.is_empty() {
+                        "none".to_string()
+                    } else {
+                        let names: Vec<_> = self.mutators.iter().map(|m| m.name()).collect();
+                        names.join(", ")
+                    },
+                    self.difficulty.xp_factor
+                ),
+                format!(
+                    "Trajectories [T]: {}{}",
+                    if self.show_trajectories { "on" } else { "off" },
+                    if self.missile_trajectories {
+                        ", incl. missiles"
+                    } else {
+                        ""
+                    }
+                ),
+                format!(
+                    "Radar: {} ({:.0} range)",
+                    self.radar_detail.name(),
+                    self.radar_range
+                ),
+            ];
+
+            let lh = 40.;
+            let th = 60.;
+            let h = 40. + th + lh * stats.len() as f32;
+            let w = 600.;
+
+            let x = screen_width() / 2. - w / 2.;
+            let y = screen_height() / 2. - h / 2.;
+
+            draw_rectangle(x, y, w, h, GRAY);
+            draw_centered_text("Stats", screen_width() / 2., y + 20., 60., BLACK);
+
+            for (idx, line) in stats.iter().enumerate() {
+                draw_text(
+                    line,
+                    x + 30.,
+                    y + th + 20. + lh * (idx + 1) as f32,
+                    30.,
+                    BLACK,
+                );
+            }
+        } else if self.paused {
+            draw_rectangle(
+                screen_width() / 2. - 100.,
+                screen_height() / 2. - 30.,
+                200.,
+                60.,
+                LIGHTGRAY,
+            );
+            draw_centered_text(
+                "PAUSE",
+                screen_width() / 2.,
+                screen_height() / 2.,
+                50.,
+                BLACK,
+            );
+        }
+    }
+}
+
+impl MainState {
+    /// Draws the world around `center` into a part of the window, or the whole window.
+    /// The `viewport` is given in logical pixels like the rest of the screen.
+    /// Inside a nebula, asteroids are only visible close to the `eye` ship, which is also the one
+    /// whose trajectories are predicted.
+    fn render_view(
+        &self,
+        center: Vec2,
+        eye: &Ship,
+        zoom: f32,
+        viewport: Option<(i32, i32, i32, i32)>,
+    ) {
+        let window = match viewport {
+            Some((_, _, w, h)) => Vec2::new(w as f32, h as f32),
+            None => Vec2::new(screen_width(), screen_height()),
+        };
+        let visible_size = window / zoom;
+        let visible_diag_length = visible_size.length();
+        // cameras take their viewport in physical pixels, macroquad 0.4.0 has no
+        // `screen_dpi_scale` yet
+        let dpi_scale = macroquad::miniquad::window::dpi_scale();
+        let viewport = viewport.map(|(x, y, w, h)| {
+            let scale = |v: i32| (v as f32 * dpi_scale) as i32;
+            (scale(x), scale(y), scale(w), scale(h))
+        });
+
+        let make_camera = |pos: Vec2| -> Camera2D {
+            let cam_pos = pos - Vec2::new(visible_size.x, -visible_size.y) / 2.;
+            let rect = Rect::new(cam_pos.x, cam_pos.y, visible_size.x, -visible_size.y);
+            Camera2D {
+                viewport,
+                ..Camera2D::from_display_rect(rect)
+            }
+        };
 
         let in_screen = |pos: Vec2, size: f32| {
-            pos.distance(self.ship.pos) < screen_diag_length / 2. + SHIP_HEIGHT + size
+            pos.distance(center) < visible_diag_length / 2. + SHIP_HEIGHT + size
         };
 
         // render stars
         let render_stars = |pos: Vec2, step: i64| {
             set_camera(&make_camera(pos));
-            let start: Vec2 = pos - 0.6 * screen_size;
-            let end = pos + 0.6 * screen_size;
+            let start: Vec2 = pos - 0.6 * visible_size;
+            let end = pos + 0.6 * visible_size;
             let c = |n: f32| -> i64 {
                 let n = n as i64;
                 n - n % step
@@ -844,14 +2325,31 @@ impl MainState {
             }
         };
 
-        render_stars(Vec2::new(2000., 2000.) + self.ship.pos / 4., 400);
-        render_stars(Vec2::new(1000., 1000.) + self.ship.pos / 2., 200);
-        render_stars(self.ship.pos, 150);
+        render_stars(Vec2::new(2000., 2000.) + center / 4., 400);
+        render_stars(Vec2::new(1000., 1000.) + center / 2., 200);
+        render_stars(center, 150);
 
-        set_camera(&make_camera(self.ship.pos));
+        set_camera(&make_camera(center));
+
+        if !self.mode.open_world() {
+            draw_rectangle_lines(0., 0., VIEW_SIZE.x, VIEW_SIZE.y, 4., GRAY);
+        }
+
+        self.render_anomalies();
 
         for bh in self.black_holes.iter() {
             draw_circle(bh.pos().x, bh.pos().y, bh.size, BLACK);
+            if let Some(expires_at) = bh.expires_at {
+                let remaining =
+                    ((expires_at - self.game_t) / self.singularity_duration).clamp(0., 1.);
+                draw_circle_lines(
+                    bh.pos().x,
+                    bh.pos().y,
+                    bh.size + 4.,
+                    2.,
+                    Color::new(0.5, 0.2, 0.8, remaining),
+                );
+            }
         }
 
         for bullet in self.bullets.iter() {
@@ -871,132 +2369,131 @@ impl MainState {
         }
 
         for asteroid in self.asteroids.iter() {
-            if in_screen(asteroid.pos, asteroid.size) {
-                let shape = &self.asteroid_shapes[asteroid.shape_idx];
-
-                shape.draw(
+            let hidden = self.biome == Biome::Nebula
+                && asteroid.pos.distance(eye.pos) > NEBULA_VISIBILITY + asteroid.size;
+            if in_screen(asteroid.pos, asteroid.size) && !hidden {
+                asteroid.shape.draw(
                     asteroid.pos.x,
                     asteroid.pos.y,
                     asteroid.size,
                     asteroid.rot,
                     2.,
-                    BLACK,
+                    asteroid.material,
                 )
             }
         }
 
+        for orb in self.xp_orbs.iter() {
+            if in_screen(orb.pos, 6.) {
+                let (x, y) = (orb.pos.x, orb.pos.y);
+                draw_poly(x, y, 4, 6., self.game_t * 90., PURPLE);
+            }
+        }
+
+        for explosion in self.explosions.iter() {
+            let progress = (self.game_t - explosion.at) / EXPLOSION_DURATION;
+            let mut color = ORANGE;
+            color.a = 0.6 * (1. - progress);
+            let (x, y) = (explosion.pos.x, explosion.pos.y);
+            draw_circle(x, y, explosion.radius * progress.sqrt(), color);
+        }
+
+        for particle in self.radiation.iter() {
+            if in_screen(particle.pos, 2.) {
+                let progress = (self.game_t - particle.at) / RADIATION_LIFETIME;
+                let mut color = GOLD;
+                color.a = 1. - progress;
+                draw_circle(particle.pos.x, particle.pos.y, 1.5 + particle.damage, color);
+            }
+        }
+
+        for number in self.damage_numbers.iter() {
+            let progress = (self.game_t - number.at) / DAMAGE_NUMBER_DURATION;
+            let mut color = MAROON;
+            color.a = 1. - progress;
+            let (x, y) = (number.pos.x, number.pos.y - 30. * progress);
+            draw_centered_text(&format!("{:.1}", number.amount), x, y, 24., color);
+        }
+
+        self.render_ship(&self.ship, BLACK);
+        if let Some(partner) = &self.partner {
+            self.render_ship(partner, coop::PARTNER_COLOR);
+            self.render_revives();
+        }
+
+        if self.show_trajectories {
+            self.render_trajectories(eye);
+        }
+    }
+
+    fn render_ship(&self, ship: &Ship, color: Color) {
+        let rotation = ship.rot.to_radians();
         let v1 = Vec2::new(
-            self.ship.pos.x + rotation.sin() * SHIP_HEIGHT / 2.,
-            self.ship.pos.y - rotation.cos() * SHIP_HEIGHT / 2.,
+            ship.pos.x + rotation.sin() * SHIP_HEIGHT / 2.,
+            ship.pos.y - rotation.cos() * SHIP_HEIGHT / 2.,
         );
         let v2 = Vec2::new(
-            self.ship.pos.x - rotation.cos() * SHIP_BASE / 2. - rotation.sin() * SHIP_HEIGHT / 2.,
-            self.ship.pos.y - rotation.sin() * SHIP_BASE / 2. + rotation.cos() * SHIP_HEIGHT / 2.,
+            ship.pos.x - rotation.cos() * SHIP_BASE / 2. - rotation.sin() * SHIP_HEIGHT / 2.,
+            ship.pos.y - rotation.sin() * SHIP_BASE / 2. + rotation.cos() * SHIP_HEIGHT / 2.,
         );
         let v3 = Vec2::new(
-            self.ship.pos.x + rotation.cos() * SHIP_BASE / 2. - rotation.sin() * SHIP_HEIGHT / 2.,
-            self.ship.pos.y + rotation.sin() * SHIP_BASE / 2. + rotation.cos() * SHIP_HEIGHT / 2.,
+            ship.pos.x + rotation.cos() * SHIP_BASE / 2. - rotation.sin() * SHIP_HEIGHT / 2.,
+            ship.pos.y + rotation.sin() * SHIP_BASE / 2. + rotation.cos() * SHIP_HEIGHT / 2.,
         );
-        draw_triangle_lines(v1, v2, v3, 2., BLACK);
-        if self.shields >= 1. {
-            let mut shield_color = if self.game_t < self.invulnerable_until {
+        if ship.down.is_some() {
+            draw_triangle_lines(v1, v2, v3, 2., GRAY);
+            return;
+        }
+        // blink while invulnerable
+        if self.game_t > ship.invulnerable_until || (self.game_t * 4.).fract() < 0.5 {
+            draw_triangle_lines(v1, v2, v3, 2., color);
+        }
+
+        if ship.shields >= 1. {
+            let mut shield_color = if self.game_t < ship.invulnerable_until {
                 RED
             } else {
                 DARKBLUE
             };
             shield_color.a = 0.5;
             draw_circle_lines(
-                self.ship.pos.x + rand::gen_range(-1., 1.),
-                self.ship.pos.y + rand::gen_range(-1., 1.),
+                ship.pos.x + rand::gen_range(-1., 1.),
+                ship.pos.y + rand::gen_range(-1., 1.),
                 0.9 * SHIP_HEIGHT,
                 1.5,
                 shield_color,
             );
         }
+    }
 
-        set_default_camera();
-
-        draw_text(
-            &format!(
-                "Fps: {}, Asteroids: {} ({}), Bullets: {}, Rockets: {}",
-                get_fps(),
-                self.asteroids.len(),
-                self.generated_asteroids,
-                self.bullets.len(),
-                self.rockets.len()
-            ),
-            30.,
-            screen_height() - 30.,
-            30.,
-            BLACK,
-        );
-
-        draw_text(
-            &format!(
-                "Level {}, XP no next Level: {}",
-                self.level,
-                self.next_level_xp - self.xp
-            ),
-            30.,
-            30.,
-            30.,
-            BLACK,
-        );
-
-        draw_text(
-            &format!(
-                "Missiles: {}  Shields: {}",
-                self.rocket_stockpile, self.shields as usize
-            ),
-            30.,
-            60.,
-            30.,
-            BLACK,
-        );
-
-        if let Some(level_up) = &self.level_up {
-            let uc = level_up.upgrade_choices.len();
-
-            let th = 60.;
-            let h = 20. + th + (80 * uc) as f32;
-            let w = 600.;
-
-            let x = screen_width() / 2. - w / 2.;
-            let y = screen_height() / 2. - h / 2.;
-
-            draw_rectangle(x, y, w, h, GRAY);
-
-            draw_centered_text("Level Up!", screen_width() / 2., y + 20., 60., BLACK);
-
-            for (idx, upgrade) in level_up.upgrade_choices.iter().enumerate() {
-                let is_selected = idx == level_up.selected;
-                let idx = idx as f32;
-                draw_rectangle(x + 20., y + idx * 80. + th + 20., w - 40., 60., BLACK);
-                let color = if is_selected { LIGHTGRAY } else { GRAY };
-                draw_rectangle(x + 25., y + idx * 80. + th + 25., w - 50., 50., color);
+    /// Ship's clock, it wobbles the more its time is dilated
+    fn render_clock(&self) {
+        let format_t = |t: f32| format!("{:02}:{:04.1}", (t / 60.) as usize, t % 60.);
+        let text = format!("Ship time {}", format_t(self.ship.t));
+
+        let distortion = 1. - self.time_dilation;
+        let color = Color::new(distortion * 0.5, 0., distortion * 0.6, 1.);
+        let font_size = 30.;
+        let width = measure_text(&text, None, font_size as u16, 1.).width;
+        let mut x = screen_width() / 2. - width / 2.;
+        for (i, c) in text.chars().enumerate() {
+            let c = c.to_string();
+            let wobble = (i as f32 * 0.8 + self.game_t * 8.).sin() * distortion * 12.;
+            draw_text(&c, x, 30. + wobble, font_size, color);
+            x += measure_text(&c, None, font_size as u16, 1.).width * (1. + distortion * 0.3);
+        }
 
-                draw_centered_text(
-                    &(upgrade.desc)(self),
-                    screen_width() / 2.,
-                    y + idx * 80. + th + 45.,
-                    50.,
-                    BLACK,
-                )
-            }
-        } else if self.paused {
-            draw_rectangle(
-                screen_width() / 2. - 100.,
-                screen_height() / 2. - 30.,
-                200.,
-                60.,
-                LIGHTGRAY,
-            );
+        if distortion > 0.01 {
             draw_centered_text(
-                "PAUSE",
+                &format!(
+                    "World time {}  (x{:.2})",
+                    format_t(self.game_t),
+                    self.time_dilation
+                ),
                 screen_width() / 2.,
-                screen_height() / 2.,
-                50.,
-                BLACK,
+                55.,
+                20.,
+                color,
             );
         }
     }
@@ -1015,41 +2512,259 @@ impl GameState for MainState {
 }
 
 struct AsteroidShape {
-    corners: Vec<(f32, f32)>,
+    /// outline around the asteroid's center, scaled so that the farthest corner is at distance 1
+    corners: Vec<Vec2>,
+    craters: Vec<(Vec2, f32)>,
+    area: f32,
 }
 
 impl AsteroidShape {
-    fn new() -> Self {
-        let mut corners = Vec::new();
+    fn generate(seed: u64) -> Self {
+        let mut rng = SeededRng::new(seed);
+
+        // a few low frequency waves for the overall form
+        let waves: Vec<(f32, f32, f32)> = [2., 3., 5.]
+            .into_iter()
+            .map(|freq| (freq, rng.gen_range(0.03, 0.1), rng.gen_range(0., 2. * PI)))
+            .collect();
+
+        // bites taken out of the outline
+        let dents: Vec<(f32, f32, f32)> = (0..rng.gen_index(0, 3))
+            .map(|_| {
+                let angle = rng.gen_range(0., 2. * PI);
+                (angle, rng.gen_range(0.3, 0.7), rng.gen_range(0.15, 0.3))
+            })
+            .collect();
+
+        let corner_count = rng.gen_index(12, 20);
+        let corners = (0..corner_count)
+            .map(|i| {
+                let angle = 2. * PI * (i as f32 + rng.gen_range(-0.3, 0.3)) / corner_count as f32;
+                let mut radius = 1. + rng.gen_range(-0.05, 0.05);
+                for &(freq, amplitude, phase) in waves.iter() {
+                    radius += amplitude * (freq * angle + phase).sin();
+                }
+                for &(dent_angle, width, depth) in dents.iter() {
+                    let diff = Vec2::from_angle(angle).angle_between(Vec2::from_angle(dent_angle));
+                    radius -= depth * (1. - diff.abs() / width).max(0.).powi(2);
+                }
+                Vec2::from_angle(angle) * radius
+            })
+            .collect();
+
+        let craters = (0..rng.gen_index(0, 4))
+            .map(|_| {
+                let center = Vec2::from_angle(rng.gen_range(0., 2. * PI)) * rng.gen_range(0., 0.5);
+                (center, rng.gen_range(0.08, 0.18))
+            })
+            .collect();
+
+        Self::from_outline(corners, craters)
+    }
 
-        for _ in 0..rand::gen_range(6, 12) {
-            let arc_offset = rand::gen_range(-0.3, 0.3);
-            let radius_factor = rand::gen_range(0.9, 1.1);
-            corners.push((arc_offset, radius_factor));
+    /// Normalizes an outline given around the origin.
+    fn from_outline(corners: Vec<Vec2>, craters: Vec<(Vec2, f32)>) -> Self {
+        let scale = corners.iter().map(|c| c.length()).fold(0., f32::max);
+        let corners: Vec<_> = corners.into_iter().map(|c| c / scale).collect();
+        let craters = craters
+            .into_iter()
+            .map(|(center, radius)| (center / scale, radius / scale))
+            .collect();
+
+        Self {
+            area: polygon_area(&corners),
+            corners,
+            craters,
         }
+    }
 
-        Self { corners }
+    fn edges(&self) -> impl Iterator<Item = (Vec2, Vec2)> + '_ {
+        let n = self.corners.len();
+        (0..n).map(move |i| (self.corners[i], self.corners[(i + 1) % n]))
     }
 
-    fn draw(&self, x: f32, y: f32, radius: f32, rotation: f32, thickness: f32, color: Color) {
-        let rot = rotation.to_radians();
-        let sides = self.corners.len();
+    fn contains(&self, p: Vec2) -> bool {
+        let mut inside = false;
+        for (a, b) in self.edges() {
+            if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+                inside = !inside;
+            }
+        }
+        inside
+    }
 
-        fn p(x: f32, y: f32, rot: f32, arc: f32, radius: f32) -> Vec2 {
-            let rx = (arc * std::f32::consts::PI * 2. + rot).cos();
-            let ry = (arc * std::f32::consts::PI * 2. + rot).sin();
+    fn distance(&self, p: Vec2) -> f32 {
+        self.edges()
+            .map(|(a, b)| {
+                let ab = b - a;
+                let t = ((p - a).dot(ab) / ab.length_squared()).clamp(0., 1.);
+                p.distance(a + ab * t)
+            })
+            .fold(f32::INFINITY, f32::min)
+    }
 
-            vec2(x + radius * rx, y + radius * ry)
+    /// Cuts the outline (rotated and scaled to world size) into `count` wedges, starting at `dir`.
+    /// Returns each fragment's offset from the current center, its shape and its size.
+    fn fragments(
+        &self,
+        rotation: Vec2,
+        size: f32,
+        dir: Vec2,
+        count: usize,
+    ) -> Vec<(Vec2, AsteroidShape, f32)> {
+        let outline: Vec<_> = self
+            .corners
+            .iter()
+            .map(|&c| rotation.rotate(c) * size)
+            .collect();
+        let craters: Vec<_> = self
+            .craters
+            .iter()
+            .map(|&(center, radius)| (rotation.rotate(center) * size, radius * size))
+            .collect();
+
+        (0..count)
+            .filter_map(|i| {
+                let d0 = Vec2::from_angle(2. * PI * i as f32 / count as f32).rotate(dir);
+                let d1 = Vec2::from_angle(2. * PI * (i + 1) as f32 / count as f32).rotate(dir);
+                let in_wedge = |p: Vec2| d0.perp_dot(p) >= 0. && p.perp_dot(d1) >= 0.;
+
+                let piece = clip_polygon(&clip_polygon(&outline, d0.perp()), -d1.perp());
+                if piece.len() < 3 || polygon_area(&piece) < 1. {
+                    return None;
+                }
+
+                let center = polygon_centroid(&piece);
+                let piece_size = piece.iter().map(|p| p.distance(center)).fold(0., f32::max);
+                let corners = piece.into_iter().map(|p| p - center).collect();
+                let craters = craters
+                    .iter()
+                    .filter(|(c, _)| in_wedge(*c))
+                    .map(|&(c, radius)| (c - center, radius))
+                    .collect();
+
+                Some((center, Self::from_outline(corners, craters), piece_size))
+            })
+            .collect()
+    }
+
+    fn draw(
+        &self,
+        x: f32,
+        y: f32,
+        radius: f32,
+        rotation: f32,
+        thickness: f32,
+        material: AsteroidMaterial,
+    ) {
+        let rot = Vec2::from_angle(rotation.to_radians());
+        let center = vec2(x, y);
+        let color = material.color();
+        let p = |c: Vec2| center + rot.rotate(c) * radius;
+
+        for (c0, c1) in self.edges() {
+            let (p0, p1) = (p(c0), p(c1));
+            draw_line(p0.x, p0.y, p1.x, p1.y, thickness, color);
+
+            match material {
+                // double hull
+                AsteroidMaterial::Iron => {
+                    let (q0, q1) = (p(c0 * 0.75), p(c1 * 0.75));
+                    draw_line(q0.x, q0.y, q1.x, q1.y, thickness, color);
+                }
+                // facets
+                AsteroidMaterial::Crystal => {
+                    draw_line(x, y, p0.x, p0.y, thickness / 2., color);
+                }
+                _ => {}
+            }
         }
 
-        for i in 0..sides {
-            let (ao, rf) = self.corners[i];
-            let p0 = p(x, y, rot, (ao + i as f32) / sides as f32, radius * rf);
+        for &(crater, crater_radius) in self.craters.iter() {
+            let c = p(crater);
+            draw_circle_lines(c.x, c.y, crater_radius * radius, thickness / 2., color);
+        }
 
-            let (ao, ro) = self.corners[(i + 1) % sides];
-            let p1 = p(x, y, rot, (ao + (i + 1) as f32) / sides as f32, radius * ro);
+        match material {
+            AsteroidMaterial::Volatile => draw_circle(x, y, radius / 5., color),
+            AsteroidMaterial::Magnetic => {
+                draw_circle_lines(x, y, radius / 3., thickness, color);
+            }
+            AsteroidMaterial::Ice => {
+                let r = radius / 2.;
+                draw_line(x - r, y, x + r, y, thickness / 2., color);
+                draw_line(x, y - r, x, y + r, thickness / 2., color);
+            }
+            _ => {}
+        }
+    }
+}
 
-            draw_line(p0.x, p0.y, p1.x, p1.y, thickness, color);
+fn polygon_area(corners: &[Vec2]) -> f32 {
+    let n = corners.len();
+    let twice_area: f32 = (0..n)
+        .map(|i| corners[i].perp_dot(corners[(i + 1) % n]))
+        .sum();
+    twice_area.abs() / 2.
+}
+
+fn polygon_centroid(corners: &[Vec2]) -> Vec2 {
+    let n = corners.len();
+    let mut twice_area = 0.;
+    let mut centroid = Vec2::ZERO;
+    for i in 0..n {
+        let (a, b) = (corners[i], corners[(i + 1) % n]);
+        let cross = a.perp_dot(b);
+        twice_area += cross;
+        centroid += (a + b) * cross;
+    }
+    centroid / (3. * twice_area)
+}
+
+/// Keeps the part of the polygon on the side of the line through the origin that `normal` points to.
+fn clip_polygon(corners: &[Vec2], normal: Vec2) -> Vec<Vec2> {
+    let n = corners.len();
+    let mut result = Vec::new();
+    for i in 0..n {
+        let (a, b) = (corners[i], corners[(i + 1) % n]);
+        let (da, db) = (a.dot(normal), b.dot(normal));
+        if da >= 0. {
+            result.push(a);
+        }
+        if (da >= 0.) != (db >= 0.) {
+            result.push(a + (b - a) * (da / (da - db)));
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_conserves_area_and_momentum() {
+        for seed in 0..20 {
+            let mut asteroid = Asteroid::generate(
+                Vec2::new(100., 50.),
+                AsteroidMaterial::Rock,
+                &mut SeededRng::new(seed),
+            );
+            asteroid.sides = 6;
+            asteroid.rot = seed as f32 * 17.;
+            asteroid.vel = Vec2::new(1.5, -0.5);
+
+            let fragments = asteroid.split(Vec2::new(0.3, 1.));
+            assert!(fragments.len() >= 2);
+
+            let mass: f32 = fragments.iter().map(|f| f.mass()).sum();
+            assert!((mass - asteroid.mass()).abs() < asteroid.mass() * 1e-3);
+
+            let momentum = fragments
+                .iter()
+                .fold(Vec2::ZERO, |acc, f| acc + f.vel * f.mass());
+            let expected = asteroid.vel * asteroid.mass();
+            assert!(momentum.abs_diff_eq(expected, expected.length() * 1e-3));
         }
     }
 }
@@ -0,0 +1,212 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+
+use super::{gravity::ForceLaw, BlackHoleEffected, MainState};
+
+const WORMHOLE_RADIUS: f32 = 30.;
+/// Wormholes pull a little, so that things find their way in
+const WORMHOLE_MASS: f32 = 2.;
+/// Objects leave a wormhole this much faster than the escape speed of its exit
+const EXIT_SPEED_FACTOR: f32 = 1.1;
+const WORMHOLE_MIN_LEVEL: usize = 3;
+const WHITE_HOLE_LEVELS: usize = 8; // one more white hole every this many levels
+
+/// Two connected ends, anything entering one leaves the other
+pub struct Wormhole {
+    pub(super) ends: [Vec2; 2],
+    pub(super) color: Color,
+}
+
+impl Wormhole {
+    /// Pull of both ends on something at `pos`
+    pub fn pull_at(&self, gravity: &ForceLaw, pos: Vec2) -> Vec2 {
+        self.ends.iter().fold(Vec2::ZERO, |acc, end| {
+            acc + gravity.acceleration(*end, WORMHOLE_MASS, pos)
+        })
+    }
+
+    /// Position and velocity with which something that entered one end leaves the other one,
+    /// keeping its direction. It leaves fast enough to escape the pull of the exit, otherwise
+    /// slow objects would fall right back in and bounce between the ends.
+    pub fn exit(
+        &self,
+        gravity: &ForceLaw,
+        pos: Vec2,
+        vel: Vec2,
+        radius: f32,
+    ) -> Option<(Vec2, Vec2)> {
+        let idx = self
+            .ends
+            .iter()
+            .position(|end| pos.distance(*end) < WORMHOLE_RADIUS)?;
+        let dir = vel.try_normalize().unwrap_or(Vec2::X);
+        let distance = WORMHOLE_RADIUS + radius + 1.;
+        let min_speed = EXIT_SPEED_FACTOR * gravity.escape_speed(WORMHOLE_MASS, distance);
+        Some((
+            self.ends[1 - idx] + dir * distance,
+            dir * vel.length().max(min_speed),
+        ))
+    }
+
+    fn traverse(&self, gravity: &ForceLaw, dt: f32, obj: &mut impl BlackHoleEffected) {
+        let pos = obj.pos();
+        *obj.vel() += self.pull_at(gravity, pos) * dt;
+
+        if let Some((pos, vel)) = self.exit(gravity, pos, *obj.vel(), obj.radius()) {
+            obj.set_pos(pos);
+            *obj.vel() = vel;
+        }
+    }
+}
+
+/// The reverse of a black hole: it pushes everything away and can't be entered
+pub struct WhiteHole {
+    pub(super) pos: Vec2,
+    pub(super) vel: Vec2,
+    pub(super) size: f32,
+}
+
+impl WhiteHole {
+    fn repel(&self, gravity: &ForceLaw, dt: f32, obj: &mut impl BlackHoleEffected) {
+        let pos = obj.pos();
+        *obj.vel() += self.push_at(gravity, pos) * dt;
+
+        // bounce off the surface
+        let offset = pos - self.pos;
+        if offset.length() < self.size + obj.radius() {
+            let normal = offset.try_normalize().unwrap_or(Vec2::X);
+            let vel = *obj.vel();
+            if vel.dot(normal) < 0. {
+                *obj.vel() = vel - 2. * vel.project_onto(normal);
+            }
+            obj.set_pos(self.pos + normal * (self.size + obj.radius()));
+        }
+    }
+
+    /// Acceleration away from the white hole of something at `pos`
+    pub fn push_at(&self, gravity: &ForceLaw, pos: Vec2) -> Vec2 {
+        -gravity.acceleration(self.pos, self.size, pos)
+    }
+}
+
+impl MainState {
+    /// Part of the gravity pass: white holes push everything away, wormholes pull and teleport
+    pub(super) fn apply_anomalies(&mut self, dt: f32) {
+        let gravity = self.gravity;
+        for white_hole in self.white_holes.iter() {
+            self.bullets
+                .iter_mut()
+                .for_each(|o| white_hole.repel(&gravity, dt, o));
+            self.rockets
+                .iter_mut()
+                .for_each(|o| white_hole.repel(&gravity, dt, o));
+            self.asteroids
+                .iter_mut()
+                .for_each(|o| white_hole.repel(&gravity, dt, o));
+            self.xp_orbs
+                .iter_mut()
+                .for_each(|o| white_hole.repel(&gravity, dt, o));
+            for ship in std::iter::once(&mut self.ship).chain(self.partner.as_mut()) {
+                if ship.down.is_none() {
+                    white_hole.repel(&gravity, dt, ship);
+                }
+            }
+        }
+
+        for wormhole in self.wormholes.iter() {
+            self.bullets
+                .iter_mut()
+                .for_each(|o| wormhole.traverse(&gravity, dt, o));
+            self.rockets
+                .iter_mut()
+                .for_each(|o| wormhole.traverse(&gravity, dt, o));
+            self.asteroids
+                .iter_mut()
+                .for_each(|o| wormhole.traverse(&gravity, dt, o));
+            self.xp_orbs
+                .iter_mut()
+                .for_each(|o| wormhole.traverse(&gravity, dt, o));
+            for ship in std::iter::once(&mut self.ship).chain(self.partner.as_mut()) {
+                if ship.down.is_none() {
+                    wormhole.traverse(&gravity, dt, ship);
+                }
+            }
+        }
+    }
+
+    /// Moves the white holes and keeps some white holes and wormholes around the ship
+    pub(super) fn update_anomalies(
+        &mut self,
+        view_diag_length: f32,
+        world_diag_length: f32,
+        dt: f32,
+    ) {
+        for white_hole in self.white_holes.iter_mut() {
+            white_hole.pos += white_hole.vel * dt;
+        }
+
+        let ship_pos = self.ship.pos;
+        let in_world = |pos: Vec2| ship_pos.distance(pos) < world_diag_length / 2.;
+        self.white_holes
+            .retain(|white_hole| in_world(white_hole.pos));
+        self.wormholes
+            .retain(|wormhole| wormhole.ends.iter().any(|end| in_world(*end)));
+
+        let random_pos = || {
+            ship_pos
+                + Vec2::from_angle(rand::gen_range(0., 2. * PI))
+                    * rand::gen_range(view_diag_length * 0.6, view_diag_length * 2.)
+        };
+
+        while self.white_holes.len() < self.level / WHITE_HOLE_LEVELS {
+            self.white_holes.push(WhiteHole {
+                pos: random_pos(),
+                vel: Vec2::from_angle(rand::gen_range(0., 2. * PI)) * rand::gen_range(0.2, 1.),
+                size: rand::gen_range(10., 25.),
+            });
+        }
+
+        if self.level >= WORMHOLE_MIN_LEVEL && self.wormholes.is_empty() {
+            self.wormholes.push(Wormhole {
+                ends: [random_pos(), random_pos()],
+                color: Color::new(rand::gen_range(0., 0.5), 0.3, rand::gen_range(0.6, 1.), 1.),
+            });
+        }
+    }
+
+    pub(super) fn render_anomalies(&self) {
+        let t = self.game_t;
+
+        for wormhole in self.wormholes.iter() {
+            for (idx, end) in wormhole.ends.iter().enumerate() {
+                // the two ends swirl in opposite directions
+                let spin = if idx == 0 { t } else { -t } * 3.;
+                for ring in 0..4 {
+                    let r = WORMHOLE_RADIUS * (1. - ring as f32 * 0.2);
+                    let mut color = wormhole.color;
+                    color.a = 1. - ring as f32 * 0.2;
+                    draw_circle_lines(end.x, end.y, r, 2., color);
+
+                    let arm = Vec2::from_angle(spin + ring as f32 * PI / 2.) * r;
+                    draw_line(end.x, end.y, end.x + arm.x, end.y + arm.y, 1., color);
+                }
+            }
+        }
+
+        for white_hole in self.white_holes.iter() {
+            let (x, y) = (white_hole.pos.x, white_hole.pos.y);
+            draw_circle(x, y, white_hole.size, WHITE);
+            draw_circle_lines(x, y, white_hole.size, 2., GOLD);
+            for i in 0..8 {
+                let dir = Vec2::from_angle(i as f32 * PI / 4. + t);
+                let pulse = (t * 4. + i as f32).sin() * 0.2 + 0.6;
+                let (p0, p1) = (
+                    white_hole.pos + dir * white_hole.size * 1.2,
+                    white_hole.pos + dir * white_hole.size * (1.4 + pulse),
+                );
+                draw_line(p0.x, p0.y, p1.x, p1.y, 1.5, GOLD);
+            }
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+
+use crate::{utils::draw_centered_text, GameState};
+
+use super::{
+    super::menu_state::MenuState, world::Biome, Asteroid, AsteroidMaterial, MainState, VIEW_SIZE,
+};
+
+pub const CLASSIC_LIVES: usize = 3;
+const CLASSIC_WAVES: usize = 10;
+const FIRST_WAVE_ASTEROIDS: usize = 4;
+const WAVE_PAUSE: f32 = 3.; // sec between a cleared wave and the next one
+/// New waves keep this distance to the ship
+const WAVE_SAFE_DISTANCE: f32 = 200.;
+
+/// Moves a position that left the screen to the opposite side
+fn wrap_around(v: Vec2) -> Vec2 {
+    let mut vr = v;
+    if vr.x > VIEW_SIZE.x {
+        vr.x = 0.;
+    }
+    if vr.x < 0. {
+        vr.x = VIEW_SIZE.x
+    }
+    if vr.y > VIEW_SIZE.y {
+        vr.y = 0.;
+    }
+    if vr.y < 0. {
+        vr.y = VIEW_SIZE.y
+    }
+    vr
+}
+
+impl MainState {
+    /// Sends in the asteroids of the next wave from the edges of the screen
+    pub(super) fn spawn_wave(&mut self) {
+        self.wave += 1;
+        self.next_wave_at = None;
+
+        let count = FIRST_WAVE_ASTEROIDS + self.wave - 1;
+        let max_speed = 1. + self.wave as f32 * 0.15;
+        for _ in 0..count {
+            let pos = loop {
+                let along = rand::gen_range(0., 2. * (VIEW_SIZE.x + VIEW_SIZE.y));
+                let pos = if along < VIEW_SIZE.x {
+                    Vec2::new(along, 0.)
+                } else if along < VIEW_SIZE.x + VIEW_SIZE.y {
+                    Vec2::new(0., along - VIEW_SIZE.x)
+                } else if along < 2. * VIEW_SIZE.x + VIEW_SIZE.y {
+                    Vec2::new(along - VIEW_SIZE.x - VIEW_SIZE.y, VIEW_SIZE.y)
+                } else {
+                    Vec2::new(VIEW_SIZE.x, along - 2. * VIEW_SIZE.x - VIEW_SIZE.y)
+                };
+                if pos.distance(self.ship.pos) > WAVE_SAFE_DISTANCE {
+                    break pos;
+                }
+            };
+            let material =
+                AsteroidMaterial::random(self.level, Biome::Field, rand::gen_range(0., 1.));
+            let mut asteroid = Asteroid::new(pos, material);
+            asteroid.vel =
+                Vec2::from_angle(rand::gen_range(0., 2. * PI)) * rand::gen_range(0.5, max_speed);
+            self.asteroids.push(asteroid);
+        }
+        self.generated_asteroids += count;
+    }
+
+    /// Keeps everything on the single screen of the classic mode
+    pub(super) fn wrap_entities(&mut self) {
+        self.ship.pos = wrap_around(self.ship.pos);
+        for bullet in self.bullets.iter_mut() {
+            bullet.pos = wrap_around(bullet.pos);
+        }
+        for rocket in self.rockets.iter_mut() {
+            rocket.pos = wrap_around(rocket.pos);
+        }
+        for asteroid in self.asteroids.iter_mut() {
+            asteroid.pos = wrap_around(asteroid.pos);
+        }
+        for orb in self.xp_orbs.iter_mut() {
+            orb.pos = wrap_around(orb.pos);
+        }
+        for particle in self.radiation.iter_mut() {
+            particle.pos = wrap_around(particle.pos);
+        }
+        for bh in self.black_holes.iter_mut() {
+            bh.body.pos = wrap_around(bh.body.pos);
+        }
+    }
+
+    /// Starts the next wave once the current one is cleared, the run is won after the last one
+    pub(super) fn update_waves(&mut self, game_t: f32) -> Option<Box<dyn GameState>> {
+        if !self.asteroids.is_empty() {
+            return None;
+        }
+
+        match self.next_wave_at {
+            None if self.wave >= CLASSIC_WAVES => {
+                return Some(Box::new(MenuState::won(self.run_stats())));
+            }
+            None => self.next_wave_at = Some(game_t + WAVE_PAUSE),
+            Some(at) if at <= game_t => self.spawn_wave(),
+            Some(_) => {}
+        }
+
+        None
+    }
+
+    pub(super) fn render_waves(&self) {
+        draw_text(
+            &format!(
+                "Wave {}/{}  Lives: {}",
+                self.wave, CLASSIC_WAVES, self.lives
+            ),
+            30.,
+            90.,
+            30.,
+            BLACK,
+        );
+
+        if let Some(at) = self.next_wave_at {
+            let mut color = DARKGRAY;
+            color.a = ((at - self.game_t) / WAVE_PAUSE).clamp(0., 1.).sqrt();
+            draw_centered_text(
+                &format!("Wave {} cleared", self.wave),
+                screen_width() / 2.,
+                screen_height() / 2. - 60.,
+                50.,
+                color,
+            );
+            draw_centered_text(
+                &format!("Wave {} incoming", self.wave + 1),
+                screen_width() / 2.,
+                screen_height() / 2.,
+                30.,
+                color,
+            );
+        }
+    }
+}
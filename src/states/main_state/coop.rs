@@ -0,0 +1,158 @@
+use macroquad::prelude::*;
+
+use crate::GameState;
+
+use super::{
+    super::{game_mode::GameMode, menu_state::MenuState},
+    Controls, MainState, Ship, RESPAWN_INVULNERABILITY, SHIP_HEIGHT, VIEW_SIZE,
+};
+
+/// Player one flies with the right hand side of the keyboard
+pub const PLAYER_ONE_CONTROLS: Controls = Controls {
+    up: KeyCode::Up,
+    down: KeyCode::Down,
+    left: KeyCode::Left,
+    right: KeyCode::Right,
+    shoot: KeyCode::RightControl,
+    rocket: KeyCode::RightShift,
+    singularity: KeyCode::RightAlt,
+    confirm: KeyCode::Enter,
+};
+
+/// Player two flies with the left hand side of the keyboard
+pub const PLAYER_TWO_CONTROLS: Controls = Controls {
+    up: KeyCode::W,
+    down: KeyCode::S,
+    left: KeyCode::A,
+    right: KeyCode::D,
+    shoot: KeyCode::Space,
+    rocket: KeyCode::LeftAlt,
+    singularity: KeyCode::LeftShift,
+    confirm: KeyCode::E,
+};
+
+pub const PARTNER_COLOR: Color = DARKGREEN;
+
+/// Time the partner has to stay close to a wreck to revive it
+const REVIVE_TIME: f32 = 3.; // sec
+const REVIVE_RANGE: f32 = 60.;
+/// Ships swallowed by a black hole leave no wreck, they come back next to their partner
+const SWALLOWED_REVIVE_TIME: f32 = 15.; // sec
+/// The ships are pulled together when they get further apart
+const LEASH_LENGTH: f32 = 1000.;
+const MIN_VIEW_SCALE: f32 = 0.5;
+
+pub struct Downed {
+    pub(super) swallowed: bool,
+    /// seconds of revival
+    pub(super) progress: f32,
+}
+
+impl MainState {
+    /// Leaves the wreck of a co-op ship behind, the run is lost when both ships are down
+    pub(super) fn wreck_ship(
+        &mut self,
+        ship_idx: usize,
+        swallowed: bool,
+    ) -> Option<Box<dyn GameState>> {
+        let partner = self.partner.as_mut()?;
+        let ship = if ship_idx == 0 {
+            &mut self.ship
+        } else {
+            partner
+        };
+        if ship.down.is_some() {
+            return None;
+        }
+        ship.down = Some(Downed {
+            swallowed,
+            progress: 0.,
+        });
+        ship.vel = Vec2::ZERO;
+
+        if self.flying_ships().next().is_none() {
+            return Some(Box::new(MenuState::lost(self.run_stats())));
+        }
+        None
+    }
+
+    /// Keeps the two ships together and revives wrecked co-op ships
+    pub(super) fn update_coop(&mut self, game_t: f32, frame_t: f32, dt: f32) {
+        let Some(partner) = &mut self.partner else {
+            return;
+        };
+
+        let offset = partner.pos - self.ship.pos;
+        let excess = offset.length() - LEASH_LENGTH;
+        if excess > 0. {
+            let pull = offset.normalize_or_zero() * excess * 0.001 * dt;
+            if self.ship.down.is_none() {
+                self.ship.vel += pull;
+            }
+            if partner.down.is_none() {
+                partner.vel -= pull;
+            }
+        }
+
+        // versus ships respawn on their own and have a screen each
+        if self.mode != GameMode::Coop {
+            return;
+        }
+
+        revive(&mut self.ship, partner, game_t, frame_t);
+        revive(partner, &self.ship, game_t, frame_t);
+
+        // zoom out so that both ships stay on the screen
+        let margin = 4. * SHIP_HEIGHT;
+        let target = (VIEW_SIZE.x / (offset.x.abs() + 2. * margin))
+            .min(VIEW_SIZE.y / (offset.y.abs() + 2. * margin))
+            .clamp(MIN_VIEW_SCALE, 1.);
+        self.view_scale += (target - self.view_scale) * (frame_t * 2.).min(1.);
+    }
+
+    /// Progress of the revivals around the wrecks
+    pub(super) fn render_revives(&self) {
+        for ship in std::iter::once(&self.ship).chain(self.partner.as_ref()) {
+            let Some(down) = &ship.down else {
+                continue;
+            };
+            if down.swallowed {
+                continue;
+            }
+            let mut color = PARTNER_COLOR;
+            color.a = 0.3;
+            draw_circle_lines(ship.pos.x, ship.pos.y, REVIVE_RANGE, 1., color);
+            color.a = 0.8;
+            let radius = REVIVE_RANGE * down.progress / REVIVE_TIME;
+            draw_circle_lines(ship.pos.x, ship.pos.y, radius, 2., color);
+        }
+    }
+}
+
+fn revive(ship: &mut Ship, partner: &Ship, game_t: f32, frame_t: f32) {
+    let Some(down) = &mut ship.down else {
+        return;
+    };
+    if partner.down.is_some() {
+        return;
+    }
+
+    let revived = if down.swallowed {
+        down.progress += frame_t;
+        down.progress >= SWALLOWED_REVIVE_TIME
+    } else {
+        if partner.pos.distance(ship.pos) < REVIVE_RANGE {
+            down.progress += frame_t;
+        }
+        down.progress >= REVIVE_TIME
+    };
+
+    if revived {
+        if down.swallowed {
+            ship.pos = partner.pos + Vec2::new(SHIP_HEIGHT * 2., 0.);
+            ship.vel = partner.vel;
+        }
+        ship.down = None;
+        ship.invulnerable_until = game_t + RESPAWN_INVULNERABILITY;
+    }
+}
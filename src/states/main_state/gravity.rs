@@ -0,0 +1,199 @@
+use macroquad::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Body {
+    pub pos: Vec2,
+    pub vel: Vec2,
+    pub mass: f32,
+}
+
+impl Body {
+    pub fn momentum(&self) -> Vec2 {
+        self.vel * self.mass
+    }
+}
+
+/// How strong gravity pulls, as `g * mass / distance^exponent`
+#[derive(Debug, Clone, Copy)]
+pub struct ForceLaw {
+    pub g: f32,
+    /// Plummer softening length, keeps the force finite close to the center of a mass
+    pub softening: f32,
+    /// 2 for Newtonian gravity
+    pub exponent: f32,
+}
+
+impl Default for ForceLaw {
+    fn default() -> Self {
+        Self {
+            g: 70.,
+            softening: 5.,
+            exponent: 2.,
+        }
+    }
+}
+
+impl ForceLaw {
+    /// Acceleration of something at `pos` towards a `mass` at `center`
+    pub fn acceleration(&self, center: Vec2, mass: f32, pos: Vec2) -> Vec2 {
+        let offset = center - pos;
+        let dist_sq = offset.length_squared() + self.softening * self.softening;
+        // the offset still has to be normalized, hence the additional power
+        let acc = offset * (self.g * mass / dist_sq.powf((self.exponent + 1.) / 2.));
+        if acc.is_finite() {
+            acc
+        } else {
+            Vec2::ZERO
+        }
+    }
+
+    /// Speed needed to get infinitely far away from a `mass` at `distance`, for exponents above 1
+    pub fn escape_speed(&self, mass: f32, distance: f32) -> f32 {
+        let dist = (distance * distance + self.softening * self.softening).sqrt();
+        let potential = self.g * mass / ((self.exponent - 1.) * dist.powf(self.exponent - 1.));
+        let speed = (2. * potential).sqrt();
+        if speed.is_finite() {
+            speed
+        } else {
+            0.
+        }
+    }
+
+    /// Accelerations of all bodies from attracting each other. Every pair pulls with opposite
+    /// forces, so the total momentum stays the same.
+    fn accelerations(&self, bodies: &[Body]) -> Vec<Vec2> {
+        let mut acc = vec![Vec2::ZERO; bodies.len()];
+        for i in 0..bodies.len() {
+            for j in (i + 1)..bodies.len() {
+                let (a, b) = (&bodies[i], &bodies[j]);
+                let unit_pull = self.acceleration(b.pos, 1., a.pos);
+                acc[i] += unit_pull * b.mass;
+                acc[j] -= unit_pull * a.mass;
+            }
+        }
+        acc
+    }
+
+    /// Advances the bodies by `dt` frames with a kick-drift-kick leapfrog,
+    /// which, unlike plain Euler integration, doesn't gain or lose energy over time.
+    pub fn step(&self, bodies: &mut [Body], dt: f32) {
+        let acc = self.accelerations(bodies);
+        for (body, acc) in bodies.iter_mut().zip(acc) {
+            body.vel += acc * dt / 2.;
+            body.pos += body.vel * dt;
+        }
+        let acc = self.accelerations(bodies);
+        for (body, acc) in bodies.iter_mut().zip(acc) {
+            body.vel += acc * dt / 2.;
+        }
+    }
+}
+
+/// Perfectly inelastic merger, the result sits at the center of mass and keeps the momentum
+pub fn merge(a: &Body, b: &Body) -> Body {
+    let mass = a.mass + b.mass;
+    if mass <= 0. {
+        return *a;
+    }
+
+    Body {
+        pos: (a.pos * a.mass + b.pos * b.mass) / mass,
+        vel: (a.momentum() + b.momentum()) / mass,
+        mass,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn total_momentum(bodies: &[Body]) -> Vec2 {
+        bodies
+            .iter()
+            .fold(Vec2::ZERO, |acc, body| acc + body.momentum())
+    }
+
+    #[test]
+    fn merge_conserves_mass_and_momentum() {
+        let a = Body {
+            pos: Vec2::new(0., 0.),
+            vel: Vec2::new(2., 1.),
+            mass: 10.,
+        };
+        let b = Body {
+            pos: Vec2::new(30., 0.),
+            vel: Vec2::new(-1., 3.),
+            mass: 30.,
+        };
+
+        let merged = merge(&a, &b);
+        assert_eq!(merged.mass, 40.);
+        assert!(merged
+            .momentum()
+            .abs_diff_eq(a.momentum() + b.momentum(), 1e-4));
+        assert!(merged.pos.abs_diff_eq(Vec2::new(22.5, 0.), 1e-4));
+    }
+
+    #[test]
+    fn attraction_conserves_momentum() {
+        let law = ForceLaw::default();
+        let mut bodies = vec![
+            Body {
+                pos: Vec2::new(0., 0.),
+                vel: Vec2::new(1., 0.),
+                mass: 5.,
+            },
+            Body {
+                pos: Vec2::new(100., 20.),
+                vel: Vec2::new(0., -1.),
+                mass: 20.,
+            },
+            Body {
+                pos: Vec2::new(-40., 80.),
+                vel: Vec2::new(0.5, 0.5),
+                mass: 12.,
+            },
+        ];
+
+        let before = total_momentum(&bodies);
+        for _ in 0..200 {
+            law.step(&mut bodies, 1.);
+        }
+        assert!(total_momentum(&bodies).abs_diff_eq(before, 1e-2));
+    }
+
+    #[test]
+    fn merging_after_infall_conserves_momentum() {
+        let law = ForceLaw::default();
+        let mut bodies = vec![
+            Body {
+                pos: Vec2::new(0., 0.),
+                vel: Vec2::new(0., 1.),
+                mass: 15.,
+            },
+            Body {
+                pos: Vec2::new(60., 0.),
+                vel: Vec2::new(-1., 0.),
+                mass: 8.,
+            },
+        ];
+        let before = total_momentum(&bodies);
+
+        while bodies[0].pos.distance(bodies[1].pos) > bodies[0].mass + bodies[1].mass {
+            law.step(&mut bodies, 1.);
+        }
+        let merged = merge(&bodies[0], &bodies[1]);
+
+        assert!(merged.momentum().abs_diff_eq(before, 1e-2));
+    }
+
+    #[test]
+    fn acceleration_stays_finite_at_the_center() {
+        let law = ForceLaw::default();
+        let acc = law.acceleration(Vec2::new(3., 4.), 100., Vec2::new(3., 4.));
+        assert_eq!(acc, Vec2::ZERO);
+
+        let close = law.acceleration(Vec2::ZERO, 100., Vec2::new(0.001, 0.));
+        assert!(close.is_finite());
+    }
+}
@@ -0,0 +1,147 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+
+use crate::utils::draw_centered_text;
+
+use super::MainState;
+
+/// A black hole counts as encountered while its pull on the ship is stronger than this
+const ENCOUNTER_PULL: f32 = 0.02; // px/frame²
+const SLINGSHOT_MIN_ANGLE: f32 = PI / 3.;
+const SLINGSHOT_MIN_SPEED_GAIN: f32 = 1.; // px/frame
+/// Maneuvers following each other within this time build up a combo
+const COMBO_WINDOW: f32 = 10.; // sec
+const CALLOUT_DURATION: f32 = 2.; // sec
+
+/// Flight close to a single black hole
+struct Encounter {
+    black_hole: u32,
+    entry_speed: f32,
+    last_offset: Vec2,
+    swept_angle: f32,
+    orbits: usize,
+    /// closest distance to the black hole relative to its size
+    closest_approach: f32,
+}
+
+struct Callout {
+    text: String,
+    at: f32,
+}
+
+#[derive(Default)]
+pub struct Maneuvers {
+    encounter: Option<Encounter>,
+    combo: usize,
+    last_maneuver_t: f32,
+    callouts: Vec<Callout>,
+}
+
+fn closeness_bonus(closest_approach: f32) -> usize {
+    if closest_approach < 4. {
+        3
+    } else if closest_approach < 8. {
+        1
+    } else {
+        0
+    }
+}
+
+impl MainState {
+    pub(super) fn track_maneuvers(&mut self) {
+        let game_t = self.game_t;
+        let maneuvers = &mut self.maneuvers;
+        if game_t - maneuvers.last_maneuver_t > COMBO_WINDOW {
+            maneuvers.combo = 0;
+        }
+        maneuvers
+            .callouts
+            .retain(|callout| callout.at + CALLOUT_DURATION > game_t);
+
+        let ship_pos = self.ship.pos;
+        let ship_speed = self.ship.vel.length();
+        let nearest = self
+            .black_holes
+            .iter()
+            .filter(|bh| !bh.collided && !bh.player_made())
+            .map(|bh| (bh, bh.pull_at(&self.gravity, ship_pos).length()))
+            .filter(|(_, pull)| *pull > ENCOUNTER_PULL)
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(bh, _)| (bh.id, ship_pos - bh.pos(), bh.size));
+
+        match (&mut self.maneuvers.encounter, nearest) {
+            (Some(encounter), Some((id, offset, size))) if encounter.black_hole == id => {
+                encounter.swept_angle += encounter.last_offset.angle_between(offset);
+                encounter.last_offset = offset;
+                encounter.closest_approach = encounter.closest_approach.min(offset.length() / size);
+
+                if encounter.swept_angle.abs() >= 2. * PI * (encounter.orbits + 1) as f32 {
+                    encounter.orbits += 1;
+                    let bonus = closeness_bonus(encounter.closest_approach);
+                    self.stats.orbits += 1;
+                    self.award_maneuver("Orbit", 5 + bonus);
+                }
+            }
+            (encounter, nearest) => {
+                if let Some(encounter) = encounter.take() {
+                    let speed_gain = ship_speed - encounter.entry_speed;
+                    if encounter.swept_angle.abs() >= SLINGSHOT_MIN_ANGLE
+                        && speed_gain >= SLINGSHOT_MIN_SPEED_GAIN
+                    {
+                        let bonus = closeness_bonus(encounter.closest_approach);
+                        self.stats.slingshots += 1;
+                        self.award_maneuver("Slingshot", 2 + bonus);
+                    }
+                }
+
+                if let Some((id, offset, size)) = nearest {
+                    self.maneuvers.encounter = Some(Encounter {
+                        black_hole: id,
+                        entry_speed: ship_speed,
+                        last_offset: offset,
+                        swept_angle: 0.,
+                        orbits: 0,
+                        closest_approach: offset.length() / size,
+                    });
+                }
+            }
+        }
+    }
+
+    fn award_maneuver(&mut self, name: &str, base_xp: usize) {
+        let maneuvers = &mut self.maneuvers;
+        maneuvers.combo += 1;
+        maneuvers.last_maneuver_t = self.game_t;
+
+        let xp = base_xp * maneuvers.combo;
+        self.stats.maneuver_xp += xp;
+        self.stats.best_combo = self.stats.best_combo.max(maneuvers.combo);
+
+        let text = if maneuvers.combo > 1 {
+            format!("{}! +{} XP (x{} combo)", name, xp, maneuvers.combo)
+        } else {
+            format!("{}! +{} XP", name, xp)
+        };
+        self.announce(text);
+        self.gain_xp(xp);
+    }
+
+    /// Shows a short message in the middle of the screen
+    pub(super) fn announce(&mut self, text: String) {
+        self.maneuvers.callouts.push(Callout {
+            text,
+            at: self.game_t,
+        });
+    }
+
+    pub(super) fn render_callouts(&self) {
+        for (idx, callout) in self.maneuvers.callouts.iter().rev().enumerate() {
+            let progress = (self.game_t - callout.at) / CALLOUT_DURATION;
+            let mut color = DARKPURPLE;
+            color.a = 1. - progress * progress;
+            let y = screen_height() / 3. - 40. * idx as f32 - 30. * progress;
+            draw_centered_text(&callout.text, screen_width() / 2., y, 40., color);
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use std::rc::Rc;
+
+use macroquad::prelude::*;
+
+use crate::net::protocol::{
+    AsteroidState, BlackHoleState, BulletState, DamageNumberState, ExplosionState, LevelUpState,
+    OrbState, RadiationState, RocketState, ShapeState, ShipState, Snapshot, WhiteHoleState,
+    WormholeState,
+};
+
+use super::{
+    super::{game_mode::GameMode, run_stats::RunStats},
+    anomalies::{WhiteHole, Wormhole},
+    coop::Downed,
+    half_kick, polygon_area,
+    world::Biome,
+    Asteroid, AsteroidMaterial, AsteroidShape, BlackHole, Bullet, DamageNumber, Explosion, LevelUp,
+    MainState, Radiation, Rocket, Ship, ShipInput, Upgrade, XpOrb, SOLO_CONTROLS,
+};
+
+/// In the order of their numbers on the wire
+const MATERIALS: [AsteroidMaterial; 6] = [
+    AsteroidMaterial::Rock,
+    AsteroidMaterial::Ice,
+    AsteroidMaterial::Iron,
+    AsteroidMaterial::Volatile,
+    AsteroidMaterial::Crystal,
+    AsteroidMaterial::Magnetic,
+];
+
+/// In the order of their numbers on the wire
+const BIOMES: [Biome; 6] = [
+    Biome::Field,
+    Biome::Belt,
+    Biome::Void,
+    Biome::Nebula,
+    Biome::IonStorm,
+    Biome::GravityWell,
+];
+
+/// The run of the online server
+impl MainState {
+    pub fn online() -> Self {
+        Self::new(GameMode::Coop, Vec::new())
+    }
+
+    /// Holds the buttons of player `player` for the next frames
+    pub fn set_input(&mut self, player: usize, input: ShipInput) {
+        let ship = match (&mut self.partner, player) {
+            (Some(partner), 1) => partner,
+            _ => &mut self.ship,
+        };
+        ship.set_input(input);
+    }
+
+    /// Advances the run by a frame of `frame_t` seconds without a window, returns the stats
+    /// once the run is over
+    pub fn simulate(&mut self, frame_t: f32) -> Option<RunStats> {
+        if self.update_level_up() {
+            return None;
+        }
+        self.step(frame_t, 1.).map(|_| self.run_stats())
+    }
+
+    /// The run as the clients see it, with the shapes of all asteroids. The server fills in
+    /// the tick and the acknowledged inputs.
+    pub fn snapshot(&mut self) -> Snapshot {
+        let mut last_id = self.last_net_id;
+        let mut net_id = |id: &mut u32| {
+            if *id == 0 {
+                last_id += 1;
+                *id = last_id;
+            }
+            *id
+        };
+
+        let asteroids = self
+            .asteroids
+            .iter_mut()
+            .map(|asteroid| AsteroidState {
+                id: net_id(&mut asteroid.net_id),
+                pos: asteroid.pos,
+                vel: asteroid.vel,
+                rot: asteroid.rot,
+                size: asteroid.size,
+                material: asteroid.material as u8,
+                hostile: asteroid.hostile,
+                shape: Some(ShapeState {
+                    corners: asteroid.shape.corners.clone(),
+                    craters: asteroid.shape.craters.clone(),
+                }),
+            })
+            .collect();
+        let bullets = self
+            .bullets
+            .iter_mut()
+            .map(|bullet| BulletState {
+                id: net_id(&mut bullet.net_id),
+                pos: bullet.pos,
+                vel: bullet.vel,
+            })
+            .collect();
+        let rockets = self
+            .rockets
+            .iter_mut()
+            .map(|rocket| RocketState {
+                id: net_id(&mut rocket.net_id),
+                pos: rocket.pos,
+                vel: rocket.vel,
+                rot: rocket.rot,
+            })
+            .collect();
+        self.last_net_id = last_id;
+
+        let partner = self.partner.as_ref().unwrap_or(&self.ship);
+        Snapshot {
+            tick: 0,
+            acks: [0; 2],
+            game_t: self.game_t,
+            level: self.level,
+            xp: self.xp,
+            next_level_xp: self.next_level_xp,
+            biome: self.biome as u8,
+            view_scale: self.view_scale,
+            ships: [ship_state(&self.ship), ship_state(partner)],
+            asteroids,
+            bullets,
+            rockets,
+            black_holes: self
+                .black_holes
+                .iter()
+                .map(|bh| BlackHoleState {
+                    id: bh.id,
+                    pos: bh.pos(),
+                    vel: bh.vel(),
+                    mass: bh.mass(),
+                    expires_at: bh.expires_at,
+                })
+                .collect(),
+            white_holes: self
+                .white_holes
+                .iter()
+                .map(|white_hole| WhiteHoleState {
+                    pos: white_hole.pos,
+                    vel: white_hole.vel,
+                    size: white_hole.size,
+                })
+                .collect(),
+            wormholes: self
+                .wormholes
+                .iter()
+                .map(|wormhole| WormholeState {
+                    ends: wormhole.ends,
+                    color: [wormhole.color.r, wormhole.color.g, wormhole.color.b],
+                })
+                .collect(),
+            xp_orbs: self
+                .xp_orbs
+                .iter()
+                .map(|orb| OrbState {
+                    pos: orb.pos,
+                    vel: orb.vel,
+                })
+                .collect(),
+            radiation: self
+                .radiation
+                .iter()
+                .map(|particle| RadiationState {
+                    pos: particle.pos,
+                    vel: particle.vel,
+                    damage: particle.damage,
+                    at: particle.at,
+                })
+                .collect(),
+            explosions: self
+                .explosions
+                .iter()
+                .map(|explosion| ExplosionState {
+                    pos: explosion.pos,
+                    radius: explosion.radius,
+                    at: explosion.at,
+                })
+                .collect(),
+            damage_numbers: self
+                .damage_numbers
+                .iter()
+                .map(|number| DamageNumberState {
+                    pos: number.pos,
+                    amount: number.amount,
+                    at: number.at,
+                })
+                .collect(),
+            level_up: self.level_up.as_ref().map(|level_up| LevelUpState {
+                choices: level_up
+                    .upgrade_choices
+                    .iter()
+                    .map(|upgrade| upgrade.label.to_string())
+                    .collect(),
+                selected: level_up.selected,
+                picker: level_up.picker,
+            }),
+            inventory: self
+                .inventory
+                .items
+                .iter()
+                .map(|(upgrade, count)| (upgrade.label.to_string(), *count))
+                .collect(),
+        }
+    }
+}
+
+fn ship_state(ship: &Ship) -> ShipState {
+    ShipState {
+        pos: ship.pos,
+        vel: ship.vel,
+        rot: ship.rot,
+        t: ship.t,
+        shields: ship.shields,
+        rocket_stockpile: ship.rocket_stockpile,
+        invulnerable_until: ship.invulnerable_until,
+        last_singularity: ship.last_singularity,
+        down: ship
+            .down
+            .as_ref()
+            .map(|down| (down.swallowed, down.progress)),
+    }
+}
+
+/// Takes everything but the position, velocity and rotation, which the client predicts for
+/// its own ship
+fn apply_ship_status(ship: &mut Ship, state: &ShipState) {
+    ship.t = state.t;
+    ship.shields = state.shields;
+    ship.rocket_stockpile = state.rocket_stockpile;
+    ship.invulnerable_until = state.invulnerable_until;
+    ship.last_singularity = state.last_singularity;
+    ship.down = state.down.map(|(swallowed, progress)| Downed {
+        swallowed,
+        progress,
+    });
+}
+
+fn apply_ship_motion(ship: &mut Ship, state: &ShipState) {
+    ship.pos = state.pos;
+    ship.vel = state.vel;
+    ship.rot = state.rot;
+}
+
+/// The copy of the server's run that an online client draws. Its `ship` is always the local
+/// player's, whatever index the server gave it.
+impl MainState {
+    pub fn mirror() -> Self {
+        let mut state = Self::new(GameMode::Coop, Vec::new());
+        // the server decides who picks, but everyone confirms with their own keyboard
+        for ship in state.ships_mut() {
+            ship.controls = SOLO_CONTROLS;
+        }
+        state
+    }
+
+    /// What the local player holds right now
+    pub fn read_input(&self) -> ShipInput {
+        self.ship.controls.read()
+    }
+
+    /// Handles the keys that only change what the client shows
+    pub fn update_mirror(&mut self) {
+        if is_key_pressed(KeyCode::Tab) {
+            self.show_stats = !self.show_stats
+        }
+
+        if is_key_pressed(KeyCode::T) {
+            self.show_trajectories = !self.show_trajectories
+        }
+    }
+
+    pub fn render_mirror(&self) {
+        self.render();
+    }
+
+    /// Flies the local ship a frame ahead of the server, the same way the server will
+    pub fn predict(&mut self, input: ShipInput) {
+        self.ship.set_input(input);
+        if self.ship.down.is_some() || self.level_up.is_some() {
+            return;
+        }
+
+        let brakes = self.has_brakes && self.difficulty.brakes;
+        let ship = std::slice::from_mut(&mut self.ship);
+        half_kick(&self.black_holes, &self.gravity, 1., ship);
+        ship[0].fly(self.biome, brakes, 1.);
+        half_kick(&self.black_holes, &self.gravity, 1., ship);
+    }
+
+    /// Resets the local ship to where the server has it and flies it again by the inputs the
+    /// server has not simulated yet
+    pub fn reconcile(&mut self, state: &ShipState, pending: impl Iterator<Item = ShipInput>) {
+        apply_ship_motion(&mut self.ship, state);
+        for input in pending {
+            self.predict(input);
+        }
+    }
+
+    /// Shows the run as in `snapshot`, `player` is the local player's index on the server
+    pub fn apply_snapshot(&mut self, snapshot: &Snapshot, player: usize, frame_time: f32) {
+        self.game_t = snapshot.game_t;
+        self.level = snapshot.level;
+        self.xp = snapshot.xp;
+        self.next_level_xp = snapshot.next_level_xp;
+        self.view_scale = snapshot.view_scale;
+        self.biome = BIOMES[snapshot.biome as usize % BIOMES.len()];
+        let target = self.biome.background();
+        let fade = (frame_time * 2.).min(1.);
+        self.background = Color::new(
+            self.background.r + (target.r - self.background.r) * fade,
+            self.background.g + (target.g - self.background.g) * fade,
+            self.background.b + (target.b - self.background.b) * fade,
+            1.,
+        );
+
+        let local = &snapshot.ships[player];
+        apply_ship_status(&mut self.ship, local);
+        // wrecks don't fly, there is nothing to predict
+        if local.down.is_some() {
+            apply_ship_motion(&mut self.ship, local);
+        }
+        if let Some(partner) = &mut self.partner {
+            let remote = &snapshot.ships[1 - player];
+            apply_ship_status(partner, remote);
+            apply_ship_motion(partner, remote);
+        }
+        self.time_dilation = self.time_dilation_at(self.ship.pos);
+
+        let game_t = self.game_t;
+        self.asteroids = snapshot
+            .asteroids
+            .iter()
+            .filter_map(|state| {
+                let shape = state.shape.as_ref()?;
+                let material = MATERIALS[state.material as usize % MATERIALS.len()];
+                Some(Asteroid {
+                    pos: state.pos,
+                    vel: state.vel,
+                    rot: state.rot,
+                    rot_speed: 0.,
+                    size: state.size,
+                    sides: 0,
+                    collided: false,
+                    shape: AsteroidShape {
+                        area: polygon_area(&shape.corners),
+                        corners: shape.corners.clone(),
+                        craters: shape.craters.clone(),
+                    },
+                    material,
+                    hp: 0.,
+                    origin: None,
+                    hostile: state.hostile,
+                    net_id: state.id,
+                })
+            })
+            .collect();
+        self.bullets = snapshot
+            .bullets
+            .iter()
+            .map(|state| Bullet {
+                pos: state.pos,
+                vel: state.vel,
+                shot_at: game_t,
+                collided: false,
+                owner: 0,
+                net_id: state.id,
+            })
+            .collect();
+        self.rockets = snapshot
+            .rockets
+            .iter()
+            .map(|state| Rocket {
+                pos: state.pos,
+                vel: state.vel,
+                rot: state.rot,
+                collided: false,
+                shot_at: game_t,
+                steer: false,
+                owner: 0,
+                net_id: state.id,
+            })
+            .collect();
+        self.black_holes = snapshot
+            .black_holes
+            .iter()
+            .map(|state| {
+                let mut bh = BlackHole::new(state.id, state.pos, state.vel, state.mass);
+                bh.expires_at = state.expires_at;
+                bh
+            })
+            .collect();
+        self.white_holes = snapshot
+            .white_holes
+            .iter()
+            .map(|state| WhiteHole {
+                pos: state.pos,
+                vel: state.vel,
+                size: state.size,
+            })
+            .collect();
+        self.wormholes = snapshot
+            .wormholes
+            .iter()
+            .map(|state| {
+                let [r, g, b] = state.color;
+                Wormhole {
+                    ends: state.ends,
+                    color: Color::new(r, g, b, 1.),
+                }
+            })
+            .collect();
+        self.xp_orbs = snapshot
+            .xp_orbs
+            .iter()
+            .map(|state| XpOrb {
+                pos: state.pos,
+                vel: state.vel,
+                xp: 0,
+                collided: false,
+            })
+            .collect();
+        self.radiation = snapshot
+            .radiation
+            .iter()
+            .map(|state| Radiation {
+                pos: state.pos,
+                vel: state.vel,
+                damage: state.damage,
+                at: state.at,
+                collided: false,
+            })
+            .collect();
+        self.explosions = snapshot
+            .explosions
+            .iter()
+            .map(|state| Explosion {
+                pos: state.pos,
+                radius: state.radius,
+                at: state.at,
+            })
+            .collect();
+        self.damage_numbers = snapshot
+            .damage_numbers
+            .iter()
+            .map(|state| DamageNumber {
+                pos: state.pos,
+                amount: state.amount,
+                at: state.at,
+            })
+            .collect();
+
+        // picking the same upgrades as on the server keeps the stats and descriptions right
+        for (label, count) in snapshot.inventory.iter() {
+            loop {
+                let owned = self
+                    .inventory
+                    .items
+                    .iter()
+                    .find(|(upgrade, _)| upgrade.label == label)
+                    .map_or(0, |(_, count)| *count);
+                if owned >= *count {
+                    break;
+                }
+                let Some(upgrade) = self.upgrade_by_label(label) else {
+                    break;
+                };
+                self.apply_upgrade(upgrade);
+            }
+        }
+
+        self.level_up = snapshot.level_up.as_ref().map(|state| LevelUp {
+            selected: state.selected,
+            upgrade_choices: state
+                .choices
+                .iter()
+                .filter_map(|label| self.upgrade_by_label(label))
+                .collect(),
+            // the local player is always player one on the client
+            picker: usize::from(state.picker != player),
+        });
+        if let Some(level_up) = &self.level_up {
+            if level_up.upgrade_choices.is_empty() {
+                self.level_up = None;
+            }
+        }
+    }
+
+    fn upgrade_by_label(&self, label: &str) -> Option<Rc<Upgrade>> {
+        self.available_upgrades
+            .iter()
+            .find(|upgrade| upgrade.label == label)
+            .cloned()
+    }
+}
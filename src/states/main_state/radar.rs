@@ -0,0 +1,98 @@
+use macroquad::prelude::*;
+
+use super::{vec_from_rot, MainState};
+
+const RADAR_RADIUS: f32 = 100.; // px
+const RADAR_MARGIN: f32 = 20.; // px
+
+/// How much the radar shows, improved by upgrades
+#[derive(Debug, Clone, Copy, Eq, PartialEq, PartialOrd, Ord)]
+pub enum RadarDetail {
+    /// Black holes and asteroids
+    Basic,
+    /// Hostile asteroids are highlighted
+    Hostiles,
+    /// Pickups are shown, asteroids by size and material
+    Full,
+}
+
+impl RadarDetail {
+    pub fn next(self) -> Self {
+        match self {
+            RadarDetail::Basic => RadarDetail::Hostiles,
+            RadarDetail::Hostiles | RadarDetail::Full => RadarDetail::Full,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RadarDetail::Basic => "Basic",
+            RadarDetail::Hostiles => "Hostile tracking",
+            RadarDetail::Full => "Full scan",
+        }
+    }
+}
+
+impl MainState {
+    pub(super) fn render_radar(&self) {
+        let center = Vec2::new(
+            screen_width() - RADAR_MARGIN - RADAR_RADIUS,
+            screen_height() - RADAR_MARGIN - RADAR_RADIUS,
+        );
+        let scale = RADAR_RADIUS / self.radar_range;
+        let to_radar = |pos: Vec2| -> Option<Vec2> {
+            let offset = (pos - self.ship.pos) * scale;
+            (offset.length() < RADAR_RADIUS).then_some(center + offset)
+        };
+
+        draw_circle(
+            center.x,
+            center.y,
+            RADAR_RADIUS,
+            Color::new(0., 0.1, 0., 0.6),
+        );
+        draw_circle_lines(center.x, center.y, RADAR_RADIUS, 2., DARKGREEN);
+        draw_circle_lines(center.x, center.y, RADAR_RADIUS / 2., 1., DARKGREEN);
+
+        for asteroid in self.asteroids.iter() {
+            if let Some(p) = to_radar(asteroid.pos) {
+                let (radius, color) = match self.radar_detail {
+                    RadarDetail::Full => {
+                        ((asteroid.size * scale).max(1.5), asteroid.material.color())
+                    }
+                    _ => (1.5, LIGHTGRAY),
+                };
+                let color = if asteroid.hostile && self.radar_detail >= RadarDetail::Hostiles {
+                    RED
+                } else {
+                    color
+                };
+                draw_circle(p.x, p.y, radius, color);
+            }
+        }
+
+        if self.radar_detail >= RadarDetail::Full {
+            for orb in self.xp_orbs.iter() {
+                if let Some(p) = to_radar(orb.pos) {
+                    draw_poly(p.x, p.y, 4, 2.5, 45., VIOLET);
+                }
+            }
+        }
+
+        for bh in self.black_holes.iter() {
+            if let Some(p) = to_radar(bh.pos()) {
+                draw_circle(p.x, p.y, (bh.size * scale * 3.).max(3.), BLACK);
+                draw_circle_lines(p.x, p.y, (bh.size * scale * 3.).max(3.), 1., WHITE);
+            }
+        }
+
+        let dir = vec_from_rot(self.ship.rot.to_radians());
+        let side = dir.perp() * 3.;
+        draw_triangle(
+            center + dir * 6.,
+            center - dir * 3. + side,
+            center - dir * 3. - side,
+            GREEN,
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use macroquad::prelude::*;
+
+use crate::GameState;
+
+use super::{
+    super::{game_mode::GameMode, menu_state::MenuState},
+    Asteroid, AsteroidMaterial, BlackHole, MainState,
+};
+
+const MIN_TIME_SCALE: f32 = 0.25;
+const MAX_TIME_SCALE: f32 = 4.;
+const SPAWNED_BLACK_HOLE_MASS: f32 = 15.;
+/// Distance from the horizon of every black hole a destroyed ship respawns at, at least
+const SAFE_SPAWN_DISTANCE: f32 = 200.; // px
+const SPAWN_SEARCH_STEP: f32 = 50.; // px
+
+impl MainState {
+    /// World position under the mouse cursor
+    fn cursor_pos(&self) -> Vec2 {
+        let screen_center = Vec2::new(screen_width(), screen_height()) / 2.;
+        self.view_center() + (Vec2::from(mouse_position()) - screen_center) / self.zoom()
+    }
+
+    /// Closest spot to `pos` that is far enough from every black hole, searched on growing rings
+    pub(super) fn safe_spawn_pos(&self, pos: Vec2) -> Vec2 {
+        let is_safe = |p: Vec2| {
+            self.black_holes
+                .iter()
+                .all(|bh| bh.pos().distance(p) > bh.size + SAFE_SPAWN_DISTANCE)
+        };
+        for ring in 0..100 {
+            let radius = ring as f32 * SPAWN_SEARCH_STEP;
+            let steps = 1 + ring * 6;
+            for step in 0..steps {
+                let angle = step as f32 / steps as f32 * 2. * std::f32::consts::PI;
+                let p = pos + Vec2::from_angle(angle) * radius;
+                if is_safe(p) {
+                    return p;
+                }
+            }
+        }
+        pos
+    }
+
+    /// Tools to practice with, they work even while the game is paused
+    pub(super) fn update_sandbox(&mut self) -> Option<Box<dyn GameState>> {
+        if is_key_pressed(KeyCode::Escape) {
+            return Some(Box::new(MenuState::returned(
+                self.mode,
+                self.mutators.clone(),
+            )));
+        }
+        if is_key_pressed(KeyCode::R) {
+            return Some(Box::new(MainState::new(
+                GameMode::Sandbox,
+                self.mutators.clone(),
+            )));
+        }
+
+        let cursor = self.cursor_pos();
+        if is_key_pressed(KeyCode::Key1) {
+            let material =
+                AsteroidMaterial::random(self.level, self.biome, rand::gen_range(0., 1.));
+            self.asteroids.push(Asteroid::new(cursor, material));
+        }
+        if is_key_pressed(KeyCode::Key2) {
+            let id = self.black_hole_id();
+            self.black_holes.push(BlackHole::new(
+                id,
+                cursor,
+                Vec2::ZERO,
+                SPAWNED_BLACK_HOLE_MASS,
+            ));
+        }
+        if is_key_pressed(KeyCode::Key3) {
+            let material =
+                AsteroidMaterial::random(self.level, self.biome, rand::gen_range(0., 1.));
+            let mut asteroid = Asteroid::new(cursor, material);
+            asteroid.hostile = true;
+            asteroid.vel = (self.ship.pos - cursor).normalize_or_zero()
+                * rand::gen_range(1., self.max_hostile_asteroid_speed);
+            self.asteroids.push(asteroid);
+        }
+
+        let upgrades = self.available_upgrades.len();
+        if upgrades > 0 {
+            if is_key_pressed(KeyCode::RightBracket) {
+                self.sandbox_upgrade += 1;
+            } else if is_key_pressed(KeyCode::LeftBracket) {
+                self.sandbox_upgrade += upgrades - 1;
+            }
+            self.sandbox_upgrade %= upgrades;
+            if is_key_pressed(KeyCode::G) {
+                let upgrade = self.available_upgrades[self.sandbox_upgrade].clone();
+                self.apply_upgrade(upgrade);
+            }
+        }
+
+        if is_key_pressed(KeyCode::I) {
+            self.invulnerable = !self.invulnerable;
+        }
+        if self.invulnerable {
+            let until = self.game_t + 0.5;
+            for ship in self.ships_mut() {
+                ship.invulnerable_until = until;
+            }
+        }
+
+        if is_key_pressed(KeyCode::Equal) {
+            self.time_scale = (self.time_scale * 2.).min(MAX_TIME_SCALE);
+        } else if is_key_pressed(KeyCode::Minus) {
+            self.time_scale = (self.time_scale / 2.).max(MIN_TIME_SCALE);
+        }
+
+        None
+    }
+
+    /// Keys of the sandbox tools
+    pub(super) fn render_sandbox(&self) {
+        let upgrade = match self.available_upgrades.get(self.sandbox_upgrade) {
+            Some(upgrade) => format!("{} {}", upgrade.label, (upgrade.desc)(self)),
+            None => "none left".to_string(),
+        };
+        let lines = [
+            "Spawn at cursor: [1] asteroid  [2] black hole  [3] hostile asteroid".to_string(),
+            format!("Upgrade [ and ]: {}  [G] grant", upgrade),
+            format!(
+                "[I] invulnerable: {}  [-/=] time x{:.2}  [R] reset  [Esc] menu",
+                if self.invulnerable { "on" } else { "off" },
+                self.time_scale
+            ),
+        ];
+        for (idx, line) in lines.iter().enumerate() {
+            draw_text(line, 30., 120. + 26. * idx as f32, 22., DARKGRAY);
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use macroquad::prelude::*;
+
+use crate::utils::draw_centered_text;
+
+use super::MainState;
+
+const EDGE_MARGIN: f32 = 30.; // px
+/// Threats arriving later than this are not indicated
+const MAX_WARNING_TIME: f32 = 10.; // sec
+/// Pull of a black hole on the ship above which the warning pulses
+const GRAVITY_WARNING_PULL: f32 = 0.05; // px/frame²
+
+impl MainState {
+    /// Seconds until an object at `pos` moving with `vel` reaches the ship, if it is closing in
+    fn time_to_impact(&self, pos: Vec2, vel: Vec2) -> Option<f32> {
+        let offset = self.ship.pos - pos;
+        let closing_speed = (vel - self.ship.vel).dot(offset.normalize_or_zero());
+        (closing_speed > 0.).then(|| offset.length() / closing_speed / 60.)
+    }
+
+    pub(super) fn render_threat_indicators(&self, zoom: f32) {
+        let screen_center = Vec2::new(screen_width(), screen_height()) / 2.;
+        let half_size = screen_center - EDGE_MARGIN;
+        // in co-op the screen is centered between the ships
+        let view_center = self.view_center();
+
+        let indicate = |pos: Vec2, vel: Vec2, black_hole: bool| {
+            let offset = (pos - view_center) * zoom;
+            if offset.x.abs() < screen_center.x && offset.y.abs() < screen_center.y {
+                return;
+            }
+            let Some(tti) = self.time_to_impact(pos, vel) else {
+                return;
+            };
+            if tti > MAX_WARNING_TIME {
+                return;
+            }
+
+            // scale the direction onto the border of the screen
+            let dir = offset.normalize();
+            let edge = (half_size / dir.abs()).min_element();
+            let p = screen_center + dir * edge;
+
+            let urgency = 1. - tti / MAX_WARNING_TIME;
+            let size = 10. + 20. * urgency;
+            let color = if tti < 3. {
+                RED
+            } else if tti < 6. {
+                ORANGE
+            } else {
+                GOLD
+            };
+
+            let side = dir.perp() * size / 2.;
+            draw_triangle(
+                p + dir * size / 2.,
+                p - dir * size / 2. + side,
+                p - dir * size / 2. - side,
+                color,
+            );
+            if black_hole {
+                let c = p - dir * size;
+                draw_circle(c.x, c.y, size / 3., BLACK);
+            }
+        };
+
+        for asteroid in self.asteroids.iter().filter(|a| a.hostile) {
+            indicate(asteroid.pos, asteroid.vel, false);
+        }
+        for bh in self.black_holes.iter() {
+            indicate(bh.pos(), bh.vel(), true);
+        }
+
+        let pull = self
+            .black_holes
+            .iter()
+            .map(|bh| bh.pull_at(&self.gravity, self.ship.pos).length())
+            .fold(0., f32::max);
+        if pull > GRAVITY_WARNING_PULL {
+            let mut color = RED;
+            color.a = 0.35 + 0.25 * (self.game_t * 10.).sin();
+            let thickness = 8. + 20. * (pull / GRAVITY_WARNING_PULL - 1.).min(1.);
+            draw_rectangle_lines(0., 0., screen_width(), screen_height(), thickness, color);
+            draw_centered_text("GRAVITY WARNING", screen_center.x, 120., 40., color);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+
+use crate::{utils::draw_centered_text, GameState};
+
+use super::{super::menu_state::MenuState, BlackHole, MainState, VIEW_SIZE};
+
+/// The event horizon swallows everything during the last part of a timed run
+const EVENT_HORIZON_PHASE: f32 = 120.; // sec before the end
+const EVENT_HORIZON_MASS: f32 = 150.;
+const EVENT_HORIZON_GROWTH: f32 = 1.; // mass/sec
+const EVENT_HORIZON_SPEED: f32 = 1.5; // px/frame
+
+/// Scripted escalation along the timeline of a timed run
+struct Milestone {
+    /// fraction of the run after which the milestone is reached
+    at: f32,
+    name: &'static str,
+    apply: fn(&mut MainState),
+}
+
+const MILESTONES: [Milestone; 4] = [
+    Milestone {
+        at: 0.2,
+        name: "Asteroid storm",
+        apply: |s| s.hostile_asteroids_per_second *= 2.,
+    },
+    Milestone {
+        at: 0.4,
+        name: "Black hole swarm",
+        apply: |s| {
+            for _ in 0..3 {
+                let pos = s.ship.pos
+                    + Vec2::from_angle(rand::gen_range(0., 2. * PI)) * VIEW_SIZE.length();
+                let vel = (s.ship.pos - pos).normalize() * rand::gen_range(1., 2.);
+                let bh = BlackHole::new(s.black_hole_id(), pos, vel, rand::gen_range(10., 25.));
+                s.black_holes.push(bh);
+            }
+        },
+    },
+    Milestone {
+        at: 0.6,
+        name: "Heavy impacts",
+        apply: |s| s.max_hostile_asteroid_speed *= 1.5,
+    },
+    Milestone {
+        at: 0.8,
+        name: "Gravity surge",
+        apply: |s| s.gravity.g *= 1.25,
+    },
+];
+
+impl MainState {
+    /// Plays the script of a timed run, the run is won when the time is up
+    pub(super) fn update_timed(&mut self, game_t: f32, frame_t: f32) -> Option<Box<dyn GameState>> {
+        let run_length = self.mode.run_length()?;
+
+        while let Some(milestone) = MILESTONES.get(self.next_milestone) {
+            if game_t < milestone.at * run_length {
+                break;
+            }
+            (milestone.apply)(self);
+            self.announce(format!("{}!", milestone.name));
+            self.next_milestone += 1;
+        }
+
+        if game_t >= run_length - EVENT_HORIZON_PHASE
+            && !self.black_holes.iter().any(|bh| bh.event_horizon)
+        {
+            let pos = self.ship.pos
+                + Vec2::from_angle(rand::gen_range(0., 2. * PI)) * VIEW_SIZE.length() * 1.5;
+            let mut bh = BlackHole::new(self.black_hole_id(), pos, Vec2::ZERO, EVENT_HORIZON_MASS);
+            bh.event_horizon = true;
+            self.black_holes.push(bh);
+            self.announce("EVENT HORIZON".to_string());
+        }
+
+        // the event horizon hunts the ship and keeps growing
+        for bh in self.black_holes.iter_mut().filter(|bh| bh.event_horizon) {
+            bh.body.vel = (self.ship.pos - bh.pos()).normalize_or_zero() * EVENT_HORIZON_SPEED;
+            bh.set_mass(bh.mass() + EVENT_HORIZON_GROWTH * frame_t);
+        }
+
+        if game_t >= run_length {
+            return Some(Box::new(MenuState::won(self.run_stats())));
+        }
+
+        None
+    }
+
+    /// Countdown until the end of a timed run
+    pub(super) fn render_countdown(&self) {
+        let Some(run_length) = self.mode.run_length() else {
+            return;
+        };
+
+        let left = (run_length - self.game_t).max(0.);
+        let color = if left < EVENT_HORIZON_PHASE {
+            RED
+        } else {
+            BLACK
+        };
+        draw_centered_text(
+            &format!(
+                "Time left {:02}:{:02}",
+                (left / 60.) as usize,
+                left as usize % 60
+            ),
+            screen_width() / 2.,
+            85.,
+            26.,
+            color,
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use macroquad::prelude::*;
+
+use super::world::Biome;
+use super::{vec_from_rot, MainState, Ship, BULLET_LIFETIME, NEBULA_MAX_SPEED, SHIP_HEIGHT};
+
+const SHIP_PREDICTION: f32 = 3.; // sec
+const MISSILE_PREDICTION: f32 = 1.; // sec
+
+impl MainState {
+    /// Integrates a path frame by frame under the pull of the black holes, which keep their
+    /// current velocity, the push of the white holes and through the wormholes.
+    /// `step` applies the object's own acceleration to its velocity.
+    fn predict_path(
+        &self,
+        mut pos: Vec2,
+        mut vel: Vec2,
+        duration: f32,
+        step: impl Fn(Vec2) -> Vec2,
+    ) -> Vec<Vec2> {
+        let mut black_holes: Vec<_> = self
+            .black_holes
+            .iter()
+            .map(|bh| (bh.pos(), bh.vel(), bh.mass(), bh.size))
+            .collect();
+
+        let mut path = vec![pos];
+        for _ in 0..(duration * 60.) as usize {
+            // the same kick-drift-kick as in the game
+            for (bh_pos, _, bh_mass, _) in black_holes.iter() {
+                vel += self.gravity.acceleration(*bh_pos, *bh_mass, pos) / 2.;
+            }
+            vel = step(vel);
+            pos += vel;
+
+            for (bh_pos, bh_vel, bh_mass, bh_size) in black_holes.iter_mut() {
+                *bh_pos += *bh_vel;
+                vel += self.gravity.acceleration(*bh_pos, *bh_mass, pos) / 2.;
+
+                if bh_pos.distance(pos) < *bh_size {
+                    path.push(pos);
+                    return path;
+                }
+            }
+            for white_hole in self.white_holes.iter() {
+                vel += white_hole.push_at(&self.gravity, pos);
+            }
+            for wormhole in self.wormholes.iter() {
+                vel += wormhole.pull_at(&self.gravity, pos);
+                if let Some(exit) = wormhole.exit(&self.gravity, pos, vel, 0.) {
+                    (pos, vel) = exit;
+                }
+            }
+            path.push(pos);
+        }
+        path
+    }
+
+    /// Predicted paths of `ship` and its next bullet, and of all missiles in flight
+    pub(super) fn render_trajectories(&self, ship: &Ship) {
+        fn draw_path(path: &[Vec2], color: Color) {
+            let len = path.len();
+            for (i, p) in path.iter().enumerate().step_by(4) {
+                let mut color = color;
+                color.a *= 1. - i as f32 / len as f32;
+                draw_circle(p.x, p.y, 1.5, color);
+            }
+        }
+
+        // ship, without thrust
+        let nebula = self.world.biome_at(ship.pos) == Biome::Nebula;
+        let max_speed = if nebula { NEBULA_MAX_SPEED } else { 5. };
+        let friction = if nebula { 100. } else { 1000. };
+        let path = self.predict_path(ship.pos, ship.vel, SHIP_PREDICTION, |vel| {
+            let vel = vel - vel / friction;
+            if vel.length() > max_speed {
+                vel.normalize() * max_speed
+            } else {
+                vel
+            }
+        });
+        draw_path(&path, DARKBLUE);
+
+        // next bullet
+        let rot_vec = vec_from_rot(ship.rot.to_radians());
+        let pos = ship.pos + rot_vec * SHIP_HEIGHT / 2.;
+        let path = self.predict_path(pos, rot_vec * 10., BULLET_LIFETIME, |vel| vel);
+        draw_path(&path, MAROON);
+
+        // missiles in flight, keeping their heading
+        if self.missile_trajectories {
+            for rocket in self.rockets.iter() {
+                let acc = 0.6 * vec_from_rot(rocket.rot.to_radians());
+                let path = self.predict_path(rocket.pos, rocket.vel, MISSILE_PREDICTION, |vel| {
+                    let vel = vel + acc;
+                    if vel.length() > 15. {
+                        vel.normalize() * 15.
+                    } else {
+                        vel
+                    }
+                });
+                draw_path(&path, DARKGREEN);
+            }
+        }
+    }
+}
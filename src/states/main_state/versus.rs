@@ -0,0 +1,131 @@
+use std::f32::consts::PI;
+
+use macroquad::prelude::*;
+
+use crate::GameState;
+
+use super::{
+    super::{
+        game_mode::{GameMode, VersusGoal},
+        menu_state::MenuState,
+    },
+    BlackHoleEffected, MainState, RESPAWN_INVULNERABILITY, ROCKET_SIZE,
+};
+
+/// Distance between the two ships at the start of a match
+pub const START_DISTANCE: f32 = 300.;
+const LIVES: usize = 3;
+const KILLS_TO_WIN: usize = 5;
+/// Destroyed ships come back this far away from their opponent
+const RESPAWN_DISTANCE: f32 = 600.;
+
+impl MainState {
+    /// Bullets and missiles hit the opponent's ship, which costs shields like an asteroid
+    pub(super) fn update_versus(&mut self, game_t: f32) -> Option<Box<dyn GameState>> {
+        let GameMode::Versus(_) = self.mode else {
+            return None;
+        };
+        let partner = self.partner.as_mut()?;
+        let hit_cost = self.difficulty.shield_hit_cost;
+
+        let mut destroyed = Vec::new();
+        for (ship_idx, ship) in [&mut self.ship, partner].into_iter().enumerate() {
+            let mut hit = false;
+            for bullet in self.bullets.iter_mut().filter(|b| b.owner != ship_idx) {
+                if !bullet.collided && bullet.pos.distance(ship.pos) < ship.radius() + 2. {
+                    bullet.collided = true;
+                    hit = true;
+                }
+            }
+            for rocket in self.rockets.iter_mut().filter(|r| r.owner != ship_idx) {
+                if !rocket.collided && rocket.pos.distance(ship.pos) < ship.radius() + ROCKET_SIZE {
+                    rocket.collided = true;
+                    hit = true;
+                }
+            }
+
+            if !hit || game_t < ship.invulnerable_until {
+                continue;
+            }
+            if ship.shields > hit_cost {
+                ship.shields -= hit_cost;
+                ship.invulnerable_until = game_t + 0.3;
+            } else {
+                destroyed.push(ship_idx);
+            }
+        }
+
+        for ship_idx in destroyed {
+            if ship_idx == 0 {
+                self.partner.as_mut()?.kills += 1;
+            } else {
+                self.ship.kills += 1;
+            }
+            if let Some(state) = self.ship_destroyed(ship_idx, false, game_t) {
+                return Some(state);
+            }
+        }
+        None
+    }
+
+    /// Respawns a destroyed ship away from its opponent, until the match is decided
+    pub(super) fn versus_ship_destroyed(
+        &mut self,
+        ship_idx: usize,
+        goal: VersusGoal,
+        game_t: f32,
+    ) -> Option<Box<dyn GameState>> {
+        let partner = self.partner.as_mut()?;
+        let (ship, opponent) = if ship_idx == 0 {
+            (&mut self.ship, partner)
+        } else {
+            (partner, &mut self.ship)
+        };
+
+        ship.deaths += 1;
+        let decided = match goal {
+            VersusGoal::Survival => ship.deaths >= LIVES,
+            VersusGoal::Kills => opponent.kills >= KILLS_TO_WIN,
+        };
+
+        ship.pos = opponent.pos + Vec2::from_angle(rand::gen_range(0., 2. * PI)) * RESPAWN_DISTANCE;
+        ship.vel = Vec2::ZERO;
+        ship.rot = 0.;
+        ship.invulnerable_until = game_t + RESPAWN_INVULNERABILITY;
+
+        if decided {
+            let mut stats = self.run_stats();
+            // players are counted from 1, the opponent of ship 0 is player 2
+            stats.winner = 2 - ship_idx;
+            return Some(Box::new(MenuState::won(stats)));
+        }
+        None
+    }
+
+    /// Divides the screen and shows the state of each player on their half
+    pub(super) fn render_versus_hud(&self, goal: VersusGoal) {
+        let Some(partner) = &self.partner else {
+            return;
+        };
+
+        let half = screen_width() / 2.;
+        draw_line(half, 0., half, screen_height(), 4., BLACK);
+
+        for (player, ship, x) in [(2, partner, 0.), (1, &self.ship, half)] {
+            let score = match goal {
+                VersusGoal::Survival => format!("Lives: {}", LIVES.saturating_sub(ship.deaths)),
+                VersusGoal::Kills => format!("Kills: {}/{}", ship.kills, KILLS_TO_WIN),
+            };
+            draw_text(
+                &format!(
+                    "P{}  {}  Missiles: {}  Shields: {}",
+                    player, score, ship.rocket_stockpile, ship.shields as usize
+                ),
+                x + 30.,
+                60.,
+                24.,
+                BLACK,
+            );
+        }
+    }
+}
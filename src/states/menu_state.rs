@@ -1,12 +1,145 @@
 use macroquad::prelude::*;
 
-use crate::GameState;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::net;
+use crate::{utils::draw_centered_text, GameState};
 
-use super::main_state::MainState;
+#[cfg(not(target_arch = "wasm32"))]
+use super::online_state::OnlineState;
+use super::{
+    daily::{DailyChallenge, DailyScores},
+    game_mode::GameMode,
+    main_state::MainState,
+    mutators::{Difficulty, Mutator},
+    run_stats::RunStats,
+};
 
-pub enum MenuState {
+pub enum MenuScreen {
     Initial,
-    Lost,
+    Lost(RunStats),
+    Won(RunStats),
+}
+
+pub struct MenuState {
+    screen: MenuScreen,
+    /// mode the next run is played in
+    mode: GameMode,
+    /// mutators of the next run, chosen with the number keys
+    mutators: Vec<Mutator>,
+    /// how a finished daily challenge compares to the days before
+    daily_results: Vec<String>,
+}
+
+impl MenuState {
+    pub fn initial() -> Self {
+        Self {
+            screen: MenuScreen::Initial,
+            mode: GameMode::default(),
+            mutators: Vec::new(),
+            daily_results: Vec::new(),
+        }
+    }
+
+    /// Back from a run that was left without an end, like a sandbox session
+    pub fn returned(mode: GameMode, mutators: Vec<Mutator>) -> Self {
+        Self {
+            mode,
+            mutators,
+            ..Self::initial()
+        }
+    }
+
+    pub fn lost(stats: RunStats) -> Self {
+        Self {
+            mode: stats.mode,
+            mutators: stats.mutators.clone(),
+            daily_results: daily_results(&stats),
+            screen: MenuScreen::Lost(stats),
+        }
+    }
+
+    pub fn won(mut stats: RunStats) -> Self {
+        stats.won = true;
+        Self {
+            mode: stats.mode,
+            mutators: stats.mutators.clone(),
+            daily_results: daily_results(&stats),
+            screen: MenuScreen::Won(stats),
+        }
+    }
+}
+
+const MUTATOR_KEYS: [KeyCode; Mutator::ALL.len()] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+];
+
+impl MenuState {
+    /// List of the mutators in the bottom left corner
+    fn render_mutators(&self) {
+        let x = 30.;
+        let mut y = screen_height() - 30. - 26. * (Mutator::ALL.len() + 1) as f32;
+        let fixed = match self.mode {
+            GameMode::Daily => Some("Mutators are fixed for the daily challenge"),
+            GameMode::Online => Some("Online runs are played without mutators"),
+            _ => None,
+        };
+        if let Some(text) = fixed {
+            draw_text(text, x, y, 22., DARKGRAY);
+            return;
+        }
+
+        let difficulty = Difficulty::with_mutators(&self.mutators);
+        draw_text(
+            &format!("Mutators (XP x{:.2}):", difficulty.xp_factor),
+            x,
+            y,
+            22.,
+            DARKGRAY,
+        );
+        for (idx, mutator) in Mutator::ALL.iter().enumerate() {
+            y += 26.;
+            let active = self.mutators.contains(mutator);
+            draw_text(
+                &format!(
+                    "[{}] {} {} (+{:.0}% XP)",
+                    idx + 1,
+                    if active { "x" } else { " " },
+                    mutator.name(),
+                    mutator.xp_bonus() * 100.
+                ),
+                x,
+                y,
+                22.,
+                if active { BLACK } else { DARKGRAY },
+            );
+        }
+    }
+}
+
+/// Records the score of a daily challenge run and compares it to the previous days.
+/// No other mode keeps scores.
+fn daily_results(stats: &RunStats) -> Vec<String> {
+    if stats.mode != GameMode::Daily {
+        return Vec::new();
+    }
+
+    let mut scores = DailyScores::load();
+    let mut lines = vec![if scores.record(stats.day, stats.score()) {
+        "Today's score has been recorded".to_string()
+    } else {
+        format!(
+            "Only the first attempt counts, today's score is {}",
+            scores.get(stats.day).unwrap_or_default()
+        )
+    }];
+    lines.extend(scores.comparison(stats.day));
+    lines
 }
 
 impl GameState for MenuState {
@@ -14,24 +147,90 @@ impl GameState for MenuState {
         clear_background(LIGHTGRAY);
         let font_size = 30.;
 
-        let text = match self {
-            MenuState::Initial => "Welcome to Asterodis. Press [enter] to play.",
-            MenuState::Lost => "Game Over. Press [enter] to play again.",
-            //MenuState::Won => "You Win!. Press [enter] to play again.",
+        if is_key_pressed(KeyCode::Right) {
+            self.mode = self.mode.next();
+        } else if is_key_pressed(KeyCode::Left) {
+            self.mode = self.mode.prev();
+        }
+
+        for (idx, mutator) in Mutator::ALL.iter().enumerate() {
+            if is_key_pressed(MUTATOR_KEYS[idx]) {
+                if let Some(pos) = self.mutators.iter().position(|m| m == mutator) {
+                    self.mutators.remove(pos);
+                } else {
+                    self.mutators.push(*mutator);
+                }
+            }
+        }
+
+        let text = match &self.screen {
+            MenuScreen::Initial => "Welcome to Asterodis. Press [enter] to play.".to_string(),
+            MenuScreen::Lost(_) => "Game Over. Press [enter] to play again.".to_string(),
+            MenuScreen::Won(stats) if stats.winner > 0 => format!(
+                "Player {} Wins!. Press [enter] to play again.",
+                stats.winner
+            ),
+            MenuScreen::Won(_) => "You Win!. Press [enter] to play again.".to_string(),
         };
 
-        let text_size = measure_text(text, None, font_size as _, 1.0);
+        let text_size = measure_text(&text, None, font_size as _, 1.0);
         draw_text(
-            text,
+            &text,
             screen_width() / 2. - text_size.width / 2.,
             screen_height() / 2. - text_size.height / 2.,
             font_size,
             DARKGRAY,
         );
-        if is_key_down(KeyCode::Enter) {
-            Some(Box::new(MainState::new()))
-        } else {
-            None
+
+        draw_centered_text(
+            &format!("Mode: < {} >", self.mode.name()),
+            screen_width() / 2.,
+            screen_height() / 2. - 50.,
+            24.,
+            DARKGRAY,
+        );
+
+        if self.mode == GameMode::Daily {
+            draw_centered_text(
+                &DailyChallenge::today().description(),
+                screen_width() / 2.,
+                screen_height() / 2. - 80.,
+                20.,
+                DARKGRAY,
+            );
+        }
+
+        if let MenuScreen::Lost(stats) | MenuScreen::Won(stats) = &self.screen {
+            for (idx, line) in stats.lines().iter().enumerate() {
+                draw_centered_text(
+                    line,
+                    screen_width() / 2.,
+                    screen_height() / 2. + 50. + 30. * idx as f32,
+                    24.,
+                    DARKGRAY,
+                );
+            }
+        }
+
+        self.render_mutators();
+
+        for (idx, line) in self.daily_results.iter().enumerate() {
+            draw_centered_text(
+                line,
+                screen_width() / 2.,
+                40. + 26. * idx as f32,
+                22.,
+                DARKGRAY,
+            );
+        }
+
+        if !is_key_down(KeyCode::Enter) {
+            return None;
+        }
+        match self.mode {
+            #[cfg(not(target_arch = "wasm32"))]
+            GameMode::Online => Some(Box::new(OnlineState::connect(&net::server_address()))),
+            _ => Some(Box::new(MainState::new(self.mode, self.mutators.clone()))),
         }
     }
 }
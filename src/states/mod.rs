@@ -1,2 +1,8 @@
+pub mod daily;
+pub mod game_mode;
 pub mod main_state;
-pub mod menu_state;
\ No newline at end of file
+pub mod menu_state;
+pub mod mutators;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod online_state;
+pub mod run_stats;
@@ -0,0 +1,186 @@
+/// Optional rules that make a run harder in exchange for more XP
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutator {
+    FastAsteroids,
+    MoreBlackHoles,
+    NoShields,
+    FragileShip,
+    GlassCannon,
+    NoBrakes,
+    MissilesOnly,
+}
+
+impl Mutator {
+    pub const ALL: [Mutator; 7] = [
+        Mutator::FastAsteroids,
+        Mutator::MoreBlackHoles,
+        Mutator::NoShields,
+        Mutator::FragileShip,
+        Mutator::GlassCannon,
+        Mutator::NoBrakes,
+        Mutator::MissilesOnly,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mutator::FastAsteroids => "Fast asteroids",
+            Mutator::MoreBlackHoles => "More black holes",
+            Mutator::NoShields => "No shields",
+            Mutator::FragileShip => "Fragile ship",
+            Mutator::GlassCannon => "Glass cannon",
+            Mutator::NoBrakes => "No brakes",
+            Mutator::MissilesOnly => "Missiles only",
+        }
+    }
+
+    /// Additional XP reward, relative to the normal one
+    pub fn xp_bonus(self) -> f32 {
+        match self {
+            Mutator::FastAsteroids => 0.25,
+            Mutator::MoreBlackHoles => 0.3,
+            Mutator::NoShields => 0.25,
+            Mutator::FragileShip => 0.15,
+            Mutator::GlassCannon => 0.1,
+            Mutator::NoBrakes => 0.1,
+            Mutator::MissilesOnly => 0.2,
+        }
+    }
+}
+
+/// Everything that makes a run harder or easier, set up once before the run
+#[derive(Debug, Clone)]
+pub struct Difficulty {
+    /// spawn rate of hostile asteroids at the start of a run
+    pub hostile_asteroids_per_second: f32,
+    pub max_hostile_asteroid_speed: f32,
+    /// factors the spawn rate and speed of hostile asteroids grow by with each level
+    pub hostile_asteroid_rate_growth: f32,
+    pub hostile_asteroid_speed_growth: f32,
+    pub black_hole_factor: usize,
+    /// shields lost by every hit
+    pub shield_hit_cost: f32,
+    pub damage_factor: f32,
+    pub xp_factor: f32,
+    pub starting_rockets: usize,
+    pub rocket_production_per_sec: f32,
+    /// whether ships can have shields at all
+    pub shields: bool,
+    pub bullets: bool,
+    pub brakes: bool,
+}
+
+impl Default for Difficulty {
+    fn default() -> Self {
+        Self {
+            hostile_asteroids_per_second: 4. / 60.,
+            max_hostile_asteroid_speed: 1.,
+            hostile_asteroid_rate_growth: 1.2,
+            hostile_asteroid_speed_growth: 1.08,
+            black_hole_factor: 1,
+            shield_hit_cost: 1.,
+            damage_factor: 1.,
+            xp_factor: 1.,
+            starting_rockets: 2,
+            rocket_production_per_sec: 0.,
+            shields: true,
+            bullets: true,
+            brakes: true,
+        }
+    }
+}
+
+impl Difficulty {
+    pub fn with_mutators(mutators: &[Mutator]) -> Self {
+        let mut difficulty = Self::default();
+        for mutator in mutators {
+            match mutator {
+                Mutator::FastAsteroids => {
+                    difficulty.hostile_asteroids_per_second *= 1.5;
+                    difficulty.max_hostile_asteroid_speed = 2.;
+                }
+                Mutator::MoreBlackHoles => difficulty.black_hole_factor *= 2,
+                Mutator::FragileShip => difficulty.shield_hit_cost *= 2.,
+                Mutator::GlassCannon => {
+                    difficulty.damage_factor *= 2.;
+                    difficulty.shield_hit_cost *= 3.;
+                }
+                Mutator::NoShields => difficulty.shields = false,
+                Mutator::MissilesOnly => {
+                    difficulty.bullets = false;
+                    difficulty.starting_rockets = 10;
+                    difficulty.rocket_production_per_sec = 0.5;
+                }
+                Mutator::NoBrakes => difficulty.brakes = false,
+            }
+            difficulty.xp_factor += mutator.xp_bonus();
+        }
+        difficulty
+    }
+
+    /// Whether the upgrade with this label makes sense at this difficulty
+    pub fn allows_upgrade(&self, label: &str) -> bool {
+        match label {
+            "BRK" => self.brakes,
+            "BRL" | "BDM" => self.bullets,
+            "SHD" => self.shields,
+            _ => true,
+        }
+    }
+
+    /// Scales gained XP by the mutators. Fractions are carried in `remainder` to the next gain,
+    /// so that small rewards are not rounded away.
+    pub fn scale_xp(&self, xp: usize, remainder: &mut f32) -> usize {
+        *remainder += xp as f32 * self.xp_factor;
+        let gained = *remainder as usize;
+        *remainder -= gained as f32;
+        gained
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_mutators_is_the_default() {
+        let difficulty = Difficulty::with_mutators(&[]);
+        let default = Difficulty::default();
+        assert_eq!(difficulty.xp_factor, default.xp_factor);
+        assert_eq!(difficulty.shield_hit_cost, default.shield_hit_cost);
+        assert_eq!(difficulty.starting_rockets, default.starting_rockets);
+        assert!(difficulty.shields);
+    }
+
+    #[test]
+    fn mutators_stack() {
+        let difficulty = Difficulty::with_mutators(&[Mutator::FragileShip, Mutator::GlassCannon]);
+        assert_eq!(difficulty.shield_hit_cost, 6.);
+        assert_eq!(difficulty.damage_factor, 2.);
+        assert_eq!(difficulty.xp_factor, 1.25);
+    }
+
+    #[test]
+    fn mutators_change_their_rules() {
+        let difficulty = Difficulty::with_mutators(&[Mutator::NoShields, Mutator::MissilesOnly]);
+        assert!(!difficulty.shields);
+        assert!(!difficulty.allows_upgrade("SHD"));
+        assert!(!difficulty.bullets);
+        assert!(!difficulty.allows_upgrade("BDM"));
+        assert!(difficulty.allows_upgrade("MDM"));
+        assert_eq!(difficulty.starting_rockets, 10);
+        assert_eq!(difficulty.rocket_production_per_sec, 0.5);
+
+        let difficulty = Difficulty::with_mutators(&[Mutator::MoreBlackHoles]);
+        assert_eq!(difficulty.black_hole_factor, 2);
+    }
+
+    #[test]
+    fn xp_fractions_carry_over() {
+        // 1.25 XP per orb
+        let difficulty = Difficulty::with_mutators(&[Mutator::FastAsteroids]);
+        let mut remainder = 0.;
+        let gained: usize = (0..4).map(|_| difficulty.scale_xp(1, &mut remainder)).sum();
+        assert_eq!(gained, 5);
+        assert_eq!(remainder, 0.);
+    }
+}
@@ -0,0 +1,174 @@
+use std::{collections::VecDeque, io};
+
+use macroquad::prelude::*;
+
+use crate::{
+    net::{
+        client::SnapshotBuffer,
+        protocol::{ClientMessage, ServerMessage, Wire, TICK_RATE},
+        Connection,
+    },
+    utils::draw_centered_text,
+    GameState,
+};
+
+use super::{
+    game_mode::GameMode,
+    main_state::{MainState, ShipInput},
+    menu_state::MenuState,
+};
+
+/// More ticks than this are not made up for after a hiccup
+const MAX_TICKS_PER_FRAME: f32 = 5.;
+
+/// Online co-op on the client: sends the local input to the server and shows the snapshots it
+/// gets back, with the local ship flown ahead by prediction
+pub struct OnlineState {
+    address: String,
+    connection: Option<Connection>,
+    /// why the connection failed or ended
+    error: Option<String>,
+    /// index of the local ship on the server, known once the server has greeted us
+    player: Option<usize>,
+    snapshots: SnapshotBuffer,
+    mirror: MainState,
+    /// inputs the server has not simulated yet, with their sequence numbers
+    pending_inputs: VecDeque<(u32, ShipInput)>,
+    last_seq: u32,
+    /// ticks that are due to be sent
+    pending_ticks: f32,
+}
+
+impl OnlineState {
+    /// Connects to the server at `address`, a failure is shown until the player goes back
+    pub fn connect(address: &str) -> Self {
+        let (connection, error) = match Connection::connect(address) {
+            Ok(connection) => (Some(connection), None),
+            Err(err) => (None, Some(err.to_string())),
+        };
+        Self {
+            address: address.to_string(),
+            connection,
+            error,
+            player: None,
+            snapshots: SnapshotBuffer::default(),
+            mirror: MainState::mirror(),
+            pending_inputs: VecDeque::new(),
+            last_seq: 0,
+            pending_ticks: 0.,
+        }
+    }
+
+    /// Handles what the server sent, returns the menu once the run is over
+    fn receive(&mut self) -> io::Result<Option<Box<dyn GameState>>> {
+        let Some(connection) = &mut self.connection else {
+            return Ok(None);
+        };
+        while let Some(bytes) = connection.receive()? {
+            match ServerMessage::from_bytes(&bytes) {
+                Some(ServerMessage::Welcome { player }) => self.player = Some(player),
+                Some(ServerMessage::Snapshot(snapshot)) => {
+                    let Some(player) = self.player else {
+                        continue;
+                    };
+                    let ack = snapshot.acks[player];
+                    self.pending_inputs.retain(|&(seq, _)| seq > ack);
+                    let pending = self.pending_inputs.iter().map(|&(_, input)| input);
+                    self.mirror.reconcile(&snapshot.ships[player], pending);
+                    self.snapshots.push(*snapshot);
+                }
+                Some(ServerMessage::GameOver(stats)) => {
+                    return Ok(Some(Box::new(MenuState::lost(stats))));
+                }
+                None => warn!("Ignoring a malformed message from the server"),
+            }
+        }
+        Ok(None)
+    }
+
+    /// Sends the local input once per server tick and flies the local ship ahead with it
+    fn send_inputs(&mut self) -> io::Result<()> {
+        let Some(connection) = &mut self.connection else {
+            return Ok(());
+        };
+        self.pending_ticks =
+            (self.pending_ticks + get_frame_time() * TICK_RATE).min(MAX_TICKS_PER_FRAME);
+        let input = self.mirror.read_input();
+        while self.pending_ticks >= 1. {
+            self.pending_ticks -= 1.;
+            self.last_seq += 1;
+            let message = ClientMessage::Input {
+                seq: self.last_seq,
+                input,
+            };
+            connection.send(message.to_bytes())?;
+            self.pending_inputs.push_back((self.last_seq, input));
+            self.mirror.predict(input);
+        }
+        Ok(())
+    }
+
+    fn render_notice(&self, text: &str) {
+        clear_background(LIGHTGRAY);
+        draw_centered_text(
+            text,
+            screen_width() / 2.,
+            screen_height() / 2.,
+            30.,
+            DARKGRAY,
+        );
+        draw_centered_text(
+            "Press [esc] to go back",
+            screen_width() / 2.,
+            screen_height() / 2. + 40.,
+            24.,
+            DARKGRAY,
+        );
+    }
+}
+
+impl GameState for OnlineState {
+    fn do_frame(&mut self) -> Option<Box<dyn GameState>> {
+        if is_key_pressed(KeyCode::Escape) {
+            return Some(Box::new(MenuState::returned(GameMode::Online, Vec::new())));
+        }
+
+        match self.receive() {
+            Ok(Some(state)) => return Some(state),
+            Ok(None) => {}
+            Err(err) => {
+                self.connection = None;
+                self.error = Some(err.to_string());
+            }
+        }
+
+        if let Some(error) = &self.error {
+            self.render_notice(&format!("No connection to {}: {}", self.address, error));
+            return None;
+        }
+        let Some(player) = self.player else {
+            self.render_notice(&format!("Connecting to {}", self.address));
+            return None;
+        };
+        if self.snapshots.latest().is_none() {
+            self.render_notice(&format!(
+                "You are player {}, waiting for the other",
+                player + 1
+            ));
+            return None;
+        }
+
+        if let Err(err) = self.send_inputs() {
+            self.connection = None;
+            self.error = Some(err.to_string());
+            return None;
+        }
+        self.mirror.update_mirror();
+        if let Some(snapshot) = self.snapshots.advance(get_frame_time()) {
+            self.mirror
+                .apply_snapshot(&snapshot, player, get_frame_time());
+        }
+        self.mirror.render_mirror();
+        None
+    }
+}
@@ -0,0 +1,69 @@
+use super::{game_mode::GameMode, mutators::Mutator};
+
+/// What happened during a run, shown when it is over
+#[derive(Debug, Clone, Default)]
+pub struct RunStats {
+    pub mode: GameMode,
+    pub time: f32,
+    pub level: usize,
+    pub asteroids_destroyed: usize,
+    pub slingshots: usize,
+    pub orbits: usize,
+    pub best_combo: usize,
+    pub maneuver_xp: usize,
+    /// wave reached in classic mode
+    pub wave: usize,
+    pub won: bool,
+    /// day of a daily challenge run
+    pub day: u32,
+    /// player who won a versus match, counted from 1
+    pub winner: usize,
+    /// opponents destroyed by player one and two in versus
+    pub kills: [usize; 2],
+    pub mutators: Vec<Mutator>,
+}
+
+impl RunStats {
+    pub fn score(&self) -> usize {
+        self.time as usize + 100 * self.level + 10 * self.asteroids_destroyed + 5 * self.maneuver_xp
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{} run, {}",
+                self.mode.name(),
+                if self.won { "won" } else { "lost" }
+            ),
+            format!(
+                "Survived {:02}:{:02}, reached level {}",
+                (self.time / 60.) as usize,
+                self.time as usize % 60,
+                self.level
+            ),
+            format!("Asteroids destroyed: {}", self.asteroids_destroyed),
+            format!(
+                "Slingshots: {}, Orbits: {}, Best combo: x{}",
+                self.slingshots, self.orbits, self.best_combo
+            ),
+            format!("XP from maneuvers: {}", self.maneuver_xp),
+        ];
+        match self.mode {
+            GameMode::Classic => lines.insert(2, format!("Reached wave {}", self.wave)),
+            GameMode::Daily => lines.insert(1, format!("Score: {}", self.score())),
+            GameMode::Versus(_) => lines.insert(
+                1,
+                format!(
+                    "Player {} wins, kills {} : {}",
+                    self.winner, self.kills[0], self.kills[1]
+                ),
+            ),
+            _ => {}
+        }
+        if !self.mutators.is_empty() {
+            let names: Vec<_> = self.mutators.iter().map(|m| m.name()).collect();
+            lines.push(format!("Mutators: {}", names.join(", ")));
+        }
+        lines
+    }
+}
@@ -1,5 +1,6 @@
 use macroquad::{
     prelude::Color,
+    rand,
     text::{draw_text, get_text_center},
 };
 
@@ -7,3 +8,39 @@ pub fn draw_centered_text(text: &str, x: f32, y: f32, font_size: f32, color: Col
     let center = get_text_center(text, None, font_size as u16, 1.0, 0.);
     draw_text(text, x - center.x, y - center.y, font_size, color)
 }
+
+/// Small deterministic random number generator (SplitMix64) for things
+/// that have to come out the same every time they are generated from the same seed.
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    /// Random float in `[low, high)`
+    pub fn gen_range(&mut self, low: f32, high: f32) -> f32 {
+        let r = (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32;
+        low + (high - low) * r
+    }
+
+    /// Random integer in `[low, high)`
+    pub fn gen_index(&mut self, low: usize, high: usize) -> usize {
+        low + (self.next_u64() % (high - low) as u64) as usize
+    }
+}
+
+/// Seed for a `SeededRng` taken from the global random number generator
+pub fn random_seed() -> u64 {
+    ((rand::rand() as u64) << 32) | rand::rand() as u64
+}